@@ -1,27 +1,171 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use clap::Parser;
-use url::ParseError;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use ethers::providers::{Http, Provider};
+use url::{ParseError, Url};
 use std::error::Error;
 use std::fmt::{Display, Debug};
-use std::net::SocketAddr;
-use std::env;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use crate::dns::{self, Dns64Config, DnsConfig, DnsLabel, DnsName, HttpsConfig, ZoneConfig};
+use crate::rpc::RpcPool;
+use crate::ens::block::{self, BlockConfig};
+use crate::ens::{chain, proof, universal, AvatarConfig, CcipConfig, ResolverBackend, TextRecordServices};
+use crate::light_client::network::NetworkConfig;
+use crate::light_client::LightClientConfig;
+use ethers::types::{Address, Chain, H256};
 
 #[derive(Parser)]
 #[clap(version = "0.1.0", author = "eth.limo")]
 pub(crate) struct Opts {
-    #[clap(short, long, env = "RPC_ENDPOINT")]
-    rpc_endpoint: Option<String>,
+    /// RPC endpoints, tried in order with failover. Defaults to http://127.0.0.1:8545 for the anvil chain
+    #[clap(short, long, env = "RPC_ENDPOINT", value_delimiter = ',')]
+    rpc_endpoint: Vec<String>,
+    /// Only serve answers that this many RPC endpoints agree on at the same block
+    #[clap(long, env = "RPC_QUORUM")]
+    rpc_quorum: Option<usize>,
+    /// Timeout of a single RPC request in milliseconds, an endpoint timing out is failed over
+    #[clap(long, env = "RPC_TIMEOUT_MS", default_value = "10000")]
+    rpc_timeout_ms: u64,
+    /// Block tag every question is resolved at: latest, safe or finalized
+    #[clap(long, env = "BLOCK_TAG", default_value = "latest")]
+    block_tag: String,
+    /// Resolve this many blocks below the block at `--block-tag`
+    #[clap(long, env = "CONFIRMATIONS", default_value = "0")]
+    confirmations: u64,
+    /// Only serve records read from `eth_getProof` storage proofs verified against the block's state root
+    #[clap(long, env = "VERIFY_PROOFS")]
+    verify_proofs: bool,
+    /// PublicResolver deployments, `--verify-proofs` only answers for resolvers running their code. Known on mainnet and sepolia
+    #[clap(long, env = "PUBLIC_RESOLVER", value_delimiter = ',')]
+    public_resolver: Vec<Address>,
+    /// Beacon API the embedded light client follows the sync committee through. Questions are then resolved at
+    /// its finalized block instead of `--block-tag`, trusting only the checkpoint
+    #[clap(long, env = "LIGHT_CLIENT_BEACON_API")]
+    light_client_beacon_api: Option<String>,
+    /// Block root of a recent finalized beacon block the light client starts from
+    #[clap(long, env = "LIGHT_CLIENT_CHECKPOINT")]
+    light_client_checkpoint: Option<H256>,
+    /// Age in seconds after which the light client's finalized block is too old to answer from
+    #[clap(long, env = "LIGHT_CLIENT_MAX_AGE", default_value = "3600")]
+    light_client_max_age: u64,
+    /// Chain the RPC endpoint serves: mainnet, sepolia, holesky, anvil or a chain id. Checked at startup
+    #[clap(long, env = "CHAIN", default_value = "mainnet")]
+    chain: String,
+    /// ENS registry address, required on chains without the canonical ENS deployment
+    #[clap(long, env = "ENS_REGISTRY")]
+    ens_registry: Option<Address>,
+    /// Keep answers until registry or resolver logs change the records they were read from
+    #[clap(long, env = "ANSWER_CACHE")]
+    answer_cache: bool,
+    /// TTL of answer records in seconds
+    #[clap(long, env = "TTL", default_value = "300")]
+    ttl: u32,
     #[clap(short, long, env = "UDP_BIND", default_value = "127.0.0.1:53")]
     udp_bind: String,
+    #[clap(long, env = "HTTPS_ALPN", value_delimiter = ',', default_value = "h2")]
+    https_alpn: Vec<String>,
+    #[clap(long, env = "HTTPS_PORT")]
+    https_port: Option<u16>,
+    /// Base64 encoded ECHConfigList of the gateway, advertised in HTTPS records using the gateway addresses
+    #[clap(long, env = "HTTPS_ECH")]
+    https_ech: Option<String>,
+    /// Gateway IPv4 addresses used as ipv4hint when a name has no A multiaddr
+    #[clap(long, env = "GATEWAY_IPV4", value_delimiter = ',')]
+    gateway_ipv4: Vec<Ipv4Addr>,
+    /// Gateway IPv6 addresses used as ipv6hint when a name has no AAAA multiaddr
+    #[clap(long, env = "GATEWAY_IPV6", value_delimiter = ',')]
+    gateway_ipv6: Vec<Ipv6Addr>,
+    /// Randomize the order of A/AAAA answers when a name publishes several multiaddrs
+    #[clap(long, env = "SHUFFLE_ADDRESS_RECORDS")]
+    shuffle_address_records: bool,
+    /// Upstream resolver used to flatten /dns4, /dns6 and /dnsaddr multiaddrs instead of answering with a CNAME
+    #[clap(long, env = "CNAME_FLATTENING_UPSTREAM")]
+    cname_flattening_upstream: Option<SocketAddr>,
+    /// Zone apex this server answers authoritatively for, e.g. `eth`
+    #[clap(long, env = "ZONE_APEX")]
+    zone_apex: Option<String>,
+    #[clap(long, env = "ZONE_NAMESERVERS", value_delimiter = ',')]
+    zone_nameservers: Vec<String>,
+    /// Responsible mailbox for the SOA record, in DNS name form (`hostmaster.example.com`)
+    #[clap(long, env = "ZONE_HOSTMASTER")]
+    zone_hostmaster: Option<String>,
+    /// JSON file mapping DNS labels to ENS text record keys (`{"_nostr": "org.nostr"}`), replaces the built-in list
+    #[clap(long, env = "TEXT_RECORDS_FILE")]
+    text_records_file: Option<String>,
+    /// Client networks (CIDR) whose ANY queries get every record of the name instead of the RFC 8482 HINFO
+    #[clap(long, env = "ANY_FULL_DUMP_CLIENTS", value_delimiter = ',')]
+    any_full_dump_clients: Vec<IpNet>,
+    /// NAT64 prefix (/32, /40, /48, /56, /64 or /96) used to synthesize AAAA records for names without an IPv6 multiaddr
+    #[clap(long, env = "DNS64_PREFIX")]
+    dns64_prefix: Option<Ipv6Net>,
+    /// IPv4 ranges that are never synthesized into DNS64 AAAA records
+    #[clap(long, env = "DNS64_EXCLUDE", value_delimiter = ',', default_value = "0.0.0.0/8,127.0.0.0/8,169.254.0.0/16,255.255.255.255/32")]
+    dns64_exclude: Vec<Ipv4Net>,
+    /// IPFS gateway used to fetch avatar NFT metadata and render `ipfs://` avatars, defaults to https://ipfs.io
+    #[clap(long, env = "AVATAR_IPFS_GATEWAY")]
+    avatar_ipfs_gateway: Option<String>,
+    /// Arweave gateway used for `ar://` avatars and metadata, defaults to https://arweave.net
+    #[clap(long, env = "AVATAR_ARWEAVE_GATEWAY")]
+    avatar_arweave_gateway: Option<String>,
+    /// Hosts of CCIP-Read (EIP-3668) gateways that may be queried, subdomains included. When empty any https gateway resolving to public addresses
+    #[clap(long, env = "CCIP_GATEWAY_ALLOWLIST", value_delimiter = ',')]
+    ccip_gateway_allowlist: Vec<String>,
+    /// Timeout of a single CCIP-Read gateway request in milliseconds
+    #[clap(long, env = "CCIP_TIMEOUT_MS", default_value = "10000")]
+    ccip_timeout_ms: u64,
+    /// Maximum number of OffchainLookup reverts followed while resolving one record
+    #[clap(long, env = "CCIP_MAX_REDIRECTS", default_value = "4")]
+    ccip_max_redirects: usize,
+    /// Resolve names with a single call to the ENS UniversalResolver instead of separate registry and resolver calls
+    #[clap(long, env = "UNIVERSAL_RESOLVER")]
+    universal_resolver: bool,
+    /// UniversalResolver address, required with `--universal-resolver` on chains without a known deployment
+    #[clap(long, env = "UNIVERSAL_RESOLVER_ADDRESS")]
+    universal_resolver_address: Option<Address>,
+}
+
+fn parse_dns_name(name: String) -> DnsName {
+    DnsName::from(name.trim_end_matches('.').to_string())
 }
 
 pub(crate) struct ResolvedOpts<T> {
     pub provider: ethers::providers::Provider<T>,
     pub udp_bind: String,
+    pub dns_config: DnsConfig,
+    pub text_records: TextRecordServices,
+    pub avatar: AvatarConfig,
+    pub ccip: CcipConfig,
+    pub chain_id: u64,
+    pub registry: Address,
+    pub backend: ResolverBackend,
+    pub block: BlockConfig,
+    pub verify_proofs: bool,
+    pub public_resolvers: Vec<Address>,
+    pub light_client: Option<LightClientConfig>,
+    pub answer_cache: bool,
 }
 
 pub(crate) enum OptionsError {
     InvalidRpcEndpoint(ParseError),
+    MissingRpcEndpoint,
+    InvalidRpcQuorum(usize, usize),
+    UnsupportedBlockTag(String),
+    ProofsRequireRegistryBackend,
+    InvalidBeaconApi(ParseError),
+    MissingLightClientCheckpoint,
+    UnsupportedLightClientChain(u64),
+    UnknownChain(String),
+    MissingEnsRegistry(u64),
+    MissingUniversalResolver(u64),
+    MissingPublicResolver(u64),
     InvalidUdpAddress,
+    MalformedEchConfig(base64::DecodeError),
+    UnreadableTextRecordsFile(std::io::Error),
+    MalformedTextRecordsFile(serde_json::Error),
+    UnsupportedNat64Prefix(Ipv6Net),
 }
 
 impl Debug for OptionsError {
@@ -29,6 +173,21 @@ impl Debug for OptionsError {
         match self {
             OptionsError::InvalidRpcEndpoint(e) => write!(f, "Invalid RPC endpoint: {}", e),
             OptionsError::InvalidUdpAddress => write!(f, "Invalid UDP address"),
+            OptionsError::MalformedEchConfig(e) => write!(f, "Malformed ECH config: {}", e),
+            OptionsError::UnreadableTextRecordsFile(e) => write!(f, "Unreadable text records file: {}", e),
+            OptionsError::MalformedTextRecordsFile(e) => write!(f, "Malformed text records file: {}", e),
+            OptionsError::UnsupportedNat64Prefix(prefix) => write!(f, "Unsupported NAT64 prefix length: {}", prefix),
+            OptionsError::MissingRpcEndpoint => write!(f, "Missing RPC endpoint"),
+            OptionsError::InvalidRpcQuorum(quorum, endpoints) => write!(f, "Invalid RPC quorum {} of {} endpoints", quorum, endpoints),
+            OptionsError::UnsupportedBlockTag(tag) => write!(f, "Unsupported block tag, expected latest, safe or finalized: {}", tag),
+            OptionsError::ProofsRequireRegistryBackend => write!(f, "Storage proofs cannot verify UniversalResolver answers"),
+            OptionsError::InvalidBeaconApi(e) => write!(f, "Invalid beacon API: {}", e),
+            OptionsError::MissingLightClientCheckpoint => write!(f, "Missing light client checkpoint, set --light-client-checkpoint"),
+            OptionsError::UnsupportedLightClientChain(chain_id) => write!(f, "No beacon chain known for chain {}", chain_id),
+            OptionsError::UnknownChain(chain) => write!(f, "Unknown chain: {}", chain),
+            OptionsError::MissingEnsRegistry(chain_id) => write!(f, "No ENS registry known for chain {}, set --ens-registry", chain_id),
+            OptionsError::MissingUniversalResolver(chain_id) => write!(f, "No UniversalResolver known for chain {}, set --universal-resolver-address", chain_id),
+            OptionsError::MissingPublicResolver(chain_id) => write!(f, "No PublicResolver known for chain {}, set --public-resolver", chain_id),
         }
     }
 }
@@ -38,31 +197,160 @@ impl Display for OptionsError {
         match self {
             OptionsError::InvalidRpcEndpoint(e) => write!(f, "Invalid RPC endpoint: {}", e),
             OptionsError::InvalidUdpAddress => write!(f, "Invalid UDP address"),
+            OptionsError::MalformedEchConfig(e) => write!(f, "Malformed ECH config: {}", e),
+            OptionsError::UnreadableTextRecordsFile(e) => write!(f, "Unreadable text records file: {}", e),
+            OptionsError::MalformedTextRecordsFile(e) => write!(f, "Malformed text records file: {}", e),
+            OptionsError::UnsupportedNat64Prefix(prefix) => write!(f, "Unsupported NAT64 prefix length: {}", prefix),
+            OptionsError::MissingRpcEndpoint => write!(f, "Missing RPC endpoint"),
+            OptionsError::InvalidRpcQuorum(quorum, endpoints) => write!(f, "Invalid RPC quorum {} of {} endpoints", quorum, endpoints),
+            OptionsError::UnsupportedBlockTag(tag) => write!(f, "Unsupported block tag, expected latest, safe or finalized: {}", tag),
+            OptionsError::ProofsRequireRegistryBackend => write!(f, "Storage proofs cannot verify UniversalResolver answers"),
+            OptionsError::InvalidBeaconApi(e) => write!(f, "Invalid beacon API: {}", e),
+            OptionsError::MissingLightClientCheckpoint => write!(f, "Missing light client checkpoint, set --light-client-checkpoint"),
+            OptionsError::UnsupportedLightClientChain(chain_id) => write!(f, "No beacon chain known for chain {}", chain_id),
+            OptionsError::UnknownChain(chain) => write!(f, "Unknown chain: {}", chain),
+            OptionsError::MissingEnsRegistry(chain_id) => write!(f, "No ENS registry known for chain {}, set --ens-registry", chain_id),
+            OptionsError::MissingUniversalResolver(chain_id) => write!(f, "No UniversalResolver known for chain {}, set --universal-resolver-address", chain_id),
+            OptionsError::MissingPublicResolver(chain_id) => write!(f, "No PublicResolver known for chain {}, set --public-resolver", chain_id),
         }
     }
 }
 
 impl Error for OptionsError {}
 
-impl TryFrom<Opts> for ResolvedOpts<ethers::providers::Http> {
+impl TryFrom<Opts> for ResolvedOpts<RpcPool<Http>> {
     type Error = OptionsError;
     fn try_from(opts: Opts) -> Result<Self, Self::Error> {
-        let rpc_endpoint = opts.rpc_endpoint.or_else(|| env::var("RPC_ENDPOINT").ok());
-        let provider = match rpc_endpoint {
-            Some(endpoint) => {
-                ethers::providers::Provider::try_from(endpoint)
-            },
-            None => {
-                Ok(ethers::providers::SEPOLIA.provider())
+        let chain_id = chain::parse_chain(&opts.chain).ok_or(OptionsError::UnknownChain(opts.chain))?;
+        let mut rpc_endpoints = opts.rpc_endpoint;
+        if rpc_endpoints.is_empty() {
+            if chain_id != Chain::AnvilHardhat as u64 {
+                return Err(OptionsError::MissingRpcEndpoint);
             }
+            rpc_endpoints.push("http://127.0.0.1:8545".to_string());
+        }
+        if let Some(quorum) = opts.rpc_quorum {
+            if quorum == 0 || quorum > rpc_endpoints.len() {
+                return Err(OptionsError::InvalidRpcQuorum(quorum, rpc_endpoints.len()));
+            }
+        }
+        let clients = rpc_endpoints
+            .iter()
+            .map(|endpoint| {
+                let url = Url::parse(endpoint).map_err(OptionsError::InvalidRpcEndpoint)?;
+                // the host only, paths and query strings often hold API keys
+                let name = url.host_str().unwrap_or_default().to_string();
+                Ok((name, Http::new(url)))
+            })
+            .collect::<Result<_, _>>()?;
+        let provider = Provider::new(RpcPool::new(clients, opts.rpc_quorum, Duration::from_millis(opts.rpc_timeout_ms)));
+        let tag = block::parse_tag(&opts.block_tag).ok_or(OptionsError::UnsupportedBlockTag(opts.block_tag))?;
+        let block = BlockConfig { tag, confirmations: opts.confirmations };
+        let registry = opts
+            .ens_registry
+            .or_else(|| chain::registry_address(chain_id))
+            .ok_or(OptionsError::MissingEnsRegistry(chain_id))?;
+        if opts.verify_proofs && opts.universal_resolver {
+            return Err(OptionsError::ProofsRequireRegistryBackend);
+        }
+        let public_resolvers = match opts.public_resolver.is_empty() {
+            true => proof::public_resolvers(chain_id),
+            false => opts.public_resolver,
+        };
+        if opts.verify_proofs && public_resolvers.is_empty() {
+            return Err(OptionsError::MissingPublicResolver(chain_id));
+        }
+        let light_client = match opts.light_client_beacon_api {
+            Some(beacon_api) => {
+                Url::parse(&beacon_api).map_err(OptionsError::InvalidBeaconApi)?;
+                Some(LightClientConfig {
+                    beacon_api,
+                    checkpoint: opts.light_client_checkpoint.ok_or(OptionsError::MissingLightClientCheckpoint)?,
+                    network: NetworkConfig::for_chain(chain_id).ok_or(OptionsError::UnsupportedLightClientChain(chain_id))?,
+                    max_age: Duration::from_secs(opts.light_client_max_age),
+                })
+            }
+            None => None,
+        };
+        let backend = if opts.universal_resolver {
+            let address = opts
+                .universal_resolver_address
+                .or_else(|| universal::universal_resolver_address(chain_id))
+                .ok_or(OptionsError::MissingUniversalResolver(chain_id))?;
+            ResolverBackend::UniversalResolver(address)
+        } else {
+            ResolverBackend::Registry
         };
-        
+
         let udp_addr = opts.udp_bind.parse::<SocketAddr>().map_err(|_| OptionsError::InvalidUdpAddress)?;
         let udp_bind = udp_addr.to_string();
 
+        let ech = opts.https_ech.map(|x| STANDARD.decode(x)).transpose().map_err(OptionsError::MalformedEchConfig)?;
+        let dns64 = match opts.dns64_prefix {
+            Some(prefix) if !dns::DNS64_PREFIX_LENGTHS.contains(&prefix.prefix_len()) => {
+                return Err(OptionsError::UnsupportedNat64Prefix(prefix));
+            }
+            Some(prefix) => Some(Dns64Config { prefix, exclude: opts.dns64_exclude }),
+            None => None,
+        };
+        let dns_config = DnsConfig {
+            https: HttpsConfig {
+                alpn: opts.https_alpn,
+                port: opts.https_port,
+                ech,
+                ipv4hint: opts.gateway_ipv4,
+                ipv6hint: opts.gateway_ipv6,
+            },
+            zone: opts.zone_apex.map(|apex| {
+                let apex = parse_dns_name(apex);
+                let hostmaster = opts.zone_hostmaster
+                    .map(parse_dns_name)
+                    .unwrap_or_else(|| DnsName { labels: [vec![DnsLabel::from("hostmaster".to_string())], apex.labels.clone()].concat() });
+                let nameservers = opts.zone_nameservers.into_iter().map(parse_dns_name).collect();
+                ZoneConfig::new(apex, nameservers, hostmaster)
+            }),
+            shuffle_address_records: opts.shuffle_address_records,
+            cname_flattening_upstream: opts.cname_flattening_upstream,
+            any_full_dump_clients: opts.any_full_dump_clients,
+            dns64,
+            ttl: opts.ttl,
+        };
+
+        let text_records = match opts.text_records_file {
+            Some(path) => {
+                let json = std::fs::read_to_string(path).map_err(OptionsError::UnreadableTextRecordsFile)?;
+                TextRecordServices::from_json(&json).map_err(OptionsError::MalformedTextRecordsFile)?
+            }
+            None => TextRecordServices::default(),
+        };
+
+        let default_avatar = AvatarConfig::default();
+        let avatar = AvatarConfig {
+            ipfs_gateway: opts.avatar_ipfs_gateway.unwrap_or(default_avatar.ipfs_gateway),
+            arweave_gateway: opts.avatar_arweave_gateway.unwrap_or(default_avatar.arweave_gateway),
+        };
+
+        let ccip = CcipConfig {
+            allowlist: opts.ccip_gateway_allowlist,
+            timeout: Duration::from_millis(opts.ccip_timeout_ms),
+            max_redirects: opts.ccip_max_redirects,
+        };
+
         Ok(ResolvedOpts {
-            provider: provider.map_err(|x| OptionsError::InvalidRpcEndpoint(x))?,
+            provider,
             udp_bind,
+            dns_config,
+            text_records,
+            avatar,
+            ccip,
+            chain_id,
+            registry,
+            backend,
+            block,
+            verify_proofs: opts.verify_proofs,
+            public_resolvers,
+            light_client,
+            answer_cache: opts.answer_cache,
         })
     }
 }
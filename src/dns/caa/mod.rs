@@ -0,0 +1,72 @@
+use super::DnsError;
+
+pub const CAA: u16 = 257;
+
+/// Issuer critical flag, RFC 8659.
+const FLAG_CRITICAL: u8 = 128;
+
+fn valid_value(tag: &str, value: &str) -> bool {
+    match tag {
+        // `<issuer domain>[; key=value...]`, an empty issuer forbids issuance
+        "issue" | "issuewild" => {
+            let issuer = value.split(';').next().unwrap_or_default().trim();
+            issuer.chars().all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '.')
+        }
+        "iodef" => value.starts_with("mailto:") || value.starts_with("https://") || value.starts_with("http://"),
+        _ => false,
+    }
+}
+
+/// `<flags> <tag> "<value>"`, only the issue, issuewild and iodef tags are served.
+fn caa_rdata(record: &str) -> Option<Vec<u8>> {
+    let record = record.trim();
+    let (flags, rest) = record.split_once(char::is_whitespace)?;
+    let (tag, value) = rest.trim_start().split_once(char::is_whitespace).unwrap_or((rest.trim_start(), ""));
+    let flags: u8 = flags.parse().ok()?;
+    let tag = tag.to_ascii_lowercase();
+    let value = value.trim();
+    let value = match value.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_string(),
+    };
+    if flags & !FLAG_CRITICAL != 0 || !valid_value(&tag, &value) {
+        return None;
+    }
+    Some([&[flags, tag.len() as u8][..], tag.as_bytes(), value.as_bytes()].concat())
+}
+
+/// Parses CAA records in presentation format, one record per line.
+pub fn parse_rdatas(answer: &str) -> Result<Vec<Vec<u8>>, DnsError> {
+    answer
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| caa_rdata(x).ok_or_else(|| DnsError::InvalidCaaRecord(x.to_string())))
+        .collect()
+}
+
+/// Renders CAA rdata in presentation format, the inverse of `parse_rdatas`.
+pub fn format_rdata(rdata: &[u8]) -> Option<String> {
+    let [flags, tag_len, rest @ ..] = rdata else {
+        return None;
+    };
+    let tag = std::str::from_utf8(rest.get(..*tag_len as usize)?).ok()?;
+    let value = std::str::from_utf8(&rest[*tag_len as usize..]).ok()?;
+    Some(format!("{} {} \"{}\"", flags, tag, value.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rdatas() {
+        let rdatas = parse_rdatas("0 issue \"letsencrypt.org\"\n128 issuewild \";\"\n0 iodef \"mailto:security@example.com\"").unwrap();
+        assert_eq!(rdatas[0], b"\x00\x05issueletsencrypt.org".to_vec());
+        assert_eq!(rdatas[1], b"\x80\x09issuewild;".to_vec());
+        assert_eq!(format_rdata(&rdatas[2]).unwrap(), "0 iodef \"mailto:security@example.com\"");
+
+        assert!(parse_rdatas("0 issuemail \"example.com\"").is_err());
+        assert!(parse_rdatas("1 issue \"example.com\"").is_err());
+        assert!(parse_rdatas("0 iodef \"ftp://example.com\"").is_err());
+    }
+}
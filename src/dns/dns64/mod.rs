@@ -0,0 +1,59 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+/// NAT64 prefix lengths with an RFC 6052 address format.
+pub const PREFIX_LENGTHS: [u8; 6] = [32, 40, 48, 56, 64, 96];
+
+/// RFC 6147 DNS64 synthesis of AAAA records from A records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dns64Config {
+    /// NAT64 prefix, `64:ff9b::/96` is the well-known prefix.
+    pub prefix: Ipv6Net,
+    /// IPv4 ranges that are never synthesized, e.g. loopback and link-local addresses.
+    pub exclude: Vec<Ipv4Net>,
+}
+
+impl Dns64Config {
+    /// Embeds `addr` in the prefix per RFC 6052, bits 64..72 (the `u` octet) are always zero.
+    /// `None` for excluded addresses or a prefix length without an address format.
+    pub fn synthesize(&self, addr: Ipv4Addr) -> Option<Ipv6Addr> {
+        if self.exclude.iter().any(|x| x.contains(&addr)) || !PREFIX_LENGTHS.contains(&self.prefix.prefix_len()) {
+            return None;
+        }
+        let mut octets = self.prefix.network().octets();
+        let start = self.prefix.prefix_len() as usize / 8;
+        let positions = (start..16).filter(|x| *x != 8);
+        for (position, octet) in positions.zip(addr.octets()) {
+            octets[position] = octet;
+        }
+        Some(Ipv6Addr::from(octets))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthesize() {
+        let addr = Ipv4Addr::new(192, 0, 2, 33);
+        // RFC 6052 section 2.4 examples
+        let expected = [
+            ("2001:db8::/32", "2001:db8:c000:221::"),
+            ("2001:db8:100::/40", "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::/48", "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::/56", "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::/64", "2001:db8:122:344:c0:2:2100:0"),
+            ("64:ff9b::/96", "64:ff9b::c000:221"),
+        ];
+        for (prefix, synthesized) in expected {
+            let config = Dns64Config { prefix: prefix.parse().unwrap(), exclude: vec![] };
+            assert_eq!(config.synthesize(addr).unwrap(), synthesized.parse::<Ipv6Addr>().unwrap());
+        }
+
+        let config = Dns64Config { prefix: "64:ff9b::/96".parse().unwrap(), exclude: vec!["192.0.2.0/24".parse().unwrap()] };
+        assert_eq!(config.synthesize(addr), None);
+        assert_eq!(config.synthesize(Ipv4Addr::new(198, 51, 100, 1)).unwrap(), "64:ff9b::c633:6401".parse::<Ipv6Addr>().unwrap());
+    }
+}
@@ -0,0 +1,105 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ethers::utils::hex;
+
+use super::DnsError;
+
+pub const TLSA: u16 = 52;
+pub const SSHFP: u16 = 44;
+pub const OPENPGPKEY: u16 = 61;
+
+fn decode_hex(fields: &[&str]) -> Option<Vec<u8>> {
+    hex::decode(fields.concat()).ok().filter(|x| !x.is_empty())
+}
+
+/// `<usage> <selector> <matching type> <hex>`, RFC 6698.
+fn tlsa_rdata(record: &str) -> Option<Vec<u8>> {
+    let fields: Vec<&str> = record.split_whitespace().collect();
+    let [usage, selector, matching_type, data @ ..] = fields.as_slice() else {
+        return None;
+    };
+    let (usage, selector, matching_type): (u8, u8, u8) = (usage.parse().ok()?, selector.parse().ok()?, matching_type.parse().ok()?);
+    let data = decode_hex(data)?;
+    let valid_length = match matching_type {
+        1 => data.len() == 32,
+        2 => data.len() == 64,
+        _ => true,
+    };
+    (usage <= 3 && selector <= 1 && valid_length).then(|| [vec![usage, selector, matching_type], data].concat())
+}
+
+/// `<algorithm> <fingerprint type> <hex>`, RFC 4255.
+fn sshfp_rdata(record: &str) -> Option<Vec<u8>> {
+    let fields: Vec<&str> = record.split_whitespace().collect();
+    let [algorithm, fingerprint_type, data @ ..] = fields.as_slice() else {
+        return None;
+    };
+    let (algorithm, fingerprint_type): (u8, u8) = (algorithm.parse().ok()?, fingerprint_type.parse().ok()?);
+    let data = decode_hex(data)?;
+    let valid_length = match fingerprint_type {
+        1 => data.len() == 20,
+        2 => data.len() == 32,
+        _ => false,
+    };
+    valid_length.then(|| [vec![algorithm, fingerprint_type], data].concat())
+}
+
+/// The base64 transferable public key, RFC 7929.
+fn openpgpkey_rdata(record: &str) -> Option<Vec<u8>> {
+    let key: String = record.split_whitespace().collect();
+    STANDARD.decode(key).ok().filter(|x| !x.is_empty())
+}
+
+/// Parses the presentation format of TLSA and SSHFP records, one record per line, or of a
+/// single OPENPGPKEY whose base64 may be wrapped over several lines.
+pub fn parse_rdatas(qtype: u16, answer: &str) -> Result<Vec<Vec<u8>>, DnsError> {
+    let parse = match qtype {
+        TLSA => tlsa_rdata,
+        SSHFP => sshfp_rdata,
+        OPENPGPKEY => {
+            return openpgpkey_rdata(answer).map(|x| vec![x]).ok_or_else(|| DnsError::InvalidKeyRecord(answer.to_string()));
+        }
+        _ => return Err(DnsError::InvalidKeyRecord(answer.to_string())),
+    };
+    answer
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| parse(x).ok_or_else(|| DnsError::InvalidKeyRecord(x.to_string())))
+        .collect()
+}
+
+/// Renders TLSA, SSHFP and OPENPGPKEY rdata in presentation format, the inverse of `parse_rdatas`.
+pub fn format_rdata(rtype: u16, rdata: &[u8]) -> Option<String> {
+    match (rtype, rdata) {
+        (TLSA, [usage, selector, matching_type, data @ ..]) => {
+            Some(format!("{} {} {} {}", usage, selector, matching_type, hex::encode(data)))
+        }
+        (SSHFP, [algorithm, fingerprint_type, data @ ..]) => {
+            Some(format!("{} {} {}", algorithm, fingerprint_type, hex::encode(data)))
+        }
+        (OPENPGPKEY, data) if !data.is_empty() => Some(STANDARD.encode(data)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rdatas() {
+        let digest = "ab".repeat(32);
+        let rdatas = parse_rdatas(TLSA, &format!("3 1 1 {}\n2 0 1 {} {}", digest, &digest[..32], &digest[32..])).unwrap();
+        assert_eq!(rdatas.len(), 2);
+        assert_eq!(rdatas[0][..3], [3, 1, 1]);
+        assert_eq!(format_rdata(TLSA, &rdatas[0]).unwrap(), format!("3 1 1 {}", digest));
+        assert!(parse_rdatas(TLSA, "3 1 1 abcd").is_err());
+
+        let rdatas = parse_rdatas(SSHFP, &format!("4 2 {}", digest)).unwrap();
+        assert_eq!(rdatas[0][..2], [4, 2]);
+        assert!(parse_rdatas(SSHFP, "4 2 zz").is_err());
+
+        assert_eq!(parse_rdatas(OPENPGPKEY, "mQIN\nBF4=").unwrap(), vec![vec![0x99, 0x02, 0x0d, 0x04, 0x5e]]);
+        assert!(parse_rdatas(OPENPGPKEY, "not base64!").is_err());
+    }
+}
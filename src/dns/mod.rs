@@ -1,17 +1,67 @@
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::error::Error;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use ethers::providers::ProviderError;
-use ipnet::AddrParseError;
+use ethers::utils::rlp::DecoderError;
+use futures_util::future::join_all;
+use ipnet::{AddrParseError, IpNet};
 use multiaddr::{Multiaddr, Protocol};
 use nom::{
     number::complete::be_u16,
     IResult,
 };
+use rand::seq::SliceRandom;
 
-pub use self::proto::{DnsQuestion, DnsName, DnsAnswerProvider, DnsHeader, DnsFlags, Parseable, Opcode, RCode};
+pub use self::proto::{DnsQuestion, DnsName, DnsLabel, DnsAnswerProvider, DnsHeader, DnsFlags, DnsRecord, Parseable, Opcode, RCode};
+pub use self::dns64::{Dns64Config, PREFIX_LENGTHS as DNS64_PREFIX_LENGTHS};
+pub use self::svcb::HttpsConfig;
+pub use self::zone::ZoneConfig;
+use self::svcb::SvcbRecord;
 
+pub mod caa;
+mod dns64;
+pub mod keys;
 mod proto;
 pub mod rule_trie;
+mod svcb;
+mod upstream;
+mod zone;
+
+/// TTL of answers unless configured otherwise.
+pub const DEFAULT_TTL: u32 = 300;
+
+/// Server wide settings for how answers are synthesized.
+#[derive(Debug, Clone)]
+pub struct DnsConfig {
+    pub https: HttpsConfig,
+    /// When set, the server answers authoritatively for names inside this zone.
+    pub zone: Option<ZoneConfig>,
+    /// Randomize the order of A/AAAA records built from multiple multiaddrs.
+    pub shuffle_address_records: bool,
+    /// Resolve hostname multiaddrs through this upstream instead of answering with a CNAME.
+    pub cname_flattening_upstream: Option<SocketAddr>,
+    /// Clients whose ANY queries are answered with every record of the name instead of HINFO.
+    pub any_full_dump_clients: Vec<IpNet>,
+    /// Synthesize AAAA records from A records for names without an IPv6 multiaddr.
+    pub dns64: Option<Dns64Config>,
+    /// TTL of every answer record.
+    pub ttl: u32,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        DnsConfig {
+            https: HttpsConfig::default(),
+            zone: None,
+            shuffle_address_records: false,
+            cname_flattening_upstream: None,
+            any_full_dump_clients: vec![],
+            dns64: None,
+            ttl: DEFAULT_TTL,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum DnsError {
@@ -19,8 +69,55 @@ pub enum DnsError {
     InvalidAddress(AddrParseError),
     EthersProviderError(ProviderError),
     ErrNoServiceTypeRecognized,
+    UpstreamError(std::io::Error),
+    InvalidUpstreamResponse,
+    EnsResolverNotFound(String),
+    InvalidResolverResponse,
+    AvatarChainUnsupported(u64),
+    AvatarNotOwned,
+    AvatarMetadataError(String),
+    InvalidEmailAuthRecord(String),
+    InvalidKeyRecord(String),
+    InvalidCaaRecord(String),
+    InvalidAtprotoDid(String),
+    CcipReadError(String),
+    BlockUnavailable(String),
+    InvalidProof(String),
+    UnverifiableRecord(String),
+    /// The light client's finalized block at this slot is too old to resolve at.
+    StaleFinalizedBlock(u64),
 }
 
+impl Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsError::InvalidMultiaddr(Some(e)) => write!(f, "Invalid multiaddr: {}", e),
+            DnsError::InvalidMultiaddr(None) => write!(f, "Invalid multiaddr"),
+            DnsError::InvalidAddress(e) => write!(f, "Invalid address: {}", e),
+            DnsError::EthersProviderError(e) => write!(f, "Ethers provider error: {}", e),
+            DnsError::ErrNoServiceTypeRecognized => write!(f, "No service type recognized"),
+            DnsError::UpstreamError(e) => write!(f, "Upstream error: {}", e),
+            DnsError::InvalidUpstreamResponse => write!(f, "Invalid upstream response"),
+            DnsError::EnsResolverNotFound(name) => write!(f, "No resolver set for {}", name),
+            DnsError::InvalidResolverResponse => write!(f, "Invalid resolver response"),
+            DnsError::AvatarChainUnsupported(chain_id) => write!(f, "Avatar NFT on unsupported chain {}", chain_id),
+            DnsError::AvatarNotOwned => write!(f, "Avatar NFT is not owned by the name's address"),
+            DnsError::AvatarMetadataError(e) => write!(f, "Avatar metadata error: {}", e),
+            DnsError::InvalidEmailAuthRecord(e) => write!(f, "Invalid email authentication record: {}", e),
+            DnsError::InvalidKeyRecord(record) => write!(f, "Invalid key record: {}", record),
+            DnsError::InvalidCaaRecord(record) => write!(f, "Invalid CAA record: {}", record),
+            DnsError::InvalidAtprotoDid(did) => write!(f, "Invalid atproto DID: {}", did),
+            DnsError::CcipReadError(e) => write!(f, "CCIP-Read error: {}", e),
+            DnsError::BlockUnavailable(tag) => write!(f, "Block {} unavailable", tag),
+            DnsError::InvalidProof(e) => write!(f, "Invalid storage proof: {}", e),
+            DnsError::UnverifiableRecord(e) => write!(f, "Record cannot be verified with storage proofs: {}", e),
+            DnsError::StaleFinalizedBlock(slot) => write!(f, "Light client finalized slot {} is too old", slot),
+        }
+    }
+}
+
+impl Error for DnsError {}
+
 impl From<multiaddr::Error> for DnsError {
     fn from(err: multiaddr::Error) -> Self {
         DnsError::InvalidMultiaddr(Some(err))
@@ -31,11 +128,21 @@ impl From<AddrParseError> for DnsError {
         DnsError::InvalidAddress(err)
     }
 }
+impl From<DecoderError> for DnsError {
+    fn from(err: DecoderError) -> Self {
+        DnsError::InvalidProof(err.to_string())
+    }
+}
 impl From<ProviderError> for DnsError {
     fn from(err: ProviderError) -> Self {
         DnsError::EthersProviderError(err)
     }
 }
+impl From<std::io::Error> for DnsError {
+    fn from(err: std::io::Error) -> Self {
+        DnsError::UpstreamError(err)
+    }
+}
 
 fn parse_dns_question(input: &[u8]) -> IResult<&[u8], DnsQuestion> {
     let (input, qname) = DnsName::parse(input)?;
@@ -58,9 +165,31 @@ fn serialize_dns_question(question: &DnsQuestion) -> Vec<u8> {
     serialized
 }
 
+/// A multiaddr from an ENS address record, reduced to what DNS answers are built from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MultiaddrEndpoint {
+    /// `Ip4`/`Ip6` or one of the hostname protocols `Dns`/`Dns4`/`Dns6`/`Dnsaddr`
+    host: Protocol<'static>,
+    tcp_port: Option<u16>,
+    udp_port: Option<u16>,
+}
+
+impl MultiaddrEndpoint {
+    fn hostname(&self) -> Option<DnsName> {
+        match &self.host {
+            Protocol::Dns(x) | Protocol::Dns4(x) | Protocol::Dns6(x) | Protocol::Dnsaddr(x) => {
+                Some(DnsName::from(x.trim_end_matches('.').to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
 struct HandleIp4Ip6Ret {
-    answers: u16,
-    response_packet: Vec<u8>,
+    records: Vec<DnsRecord>,
+    /// Set when there are no addresses of the queried family, only a hostname based multiaddr
+    cname_target: Option<DnsName>,
+    errors: Vec<DnsError>,
 }
 
 trait SelectCorrectMultiAddrProtocol<T> {
@@ -71,6 +200,9 @@ impl SelectCorrectMultiAddrProtocol<Ipv4Addr> for Ipv4Addr {
     fn select_protocol<'a>(p: Protocol<'a>) -> Option<Protocol<'a>> {
         match p {
             Protocol::Ip4(x) => Some(Protocol::Ip4(x)),
+            Protocol::Dns4(x) => Some(Protocol::Dns4(x)),
+            Protocol::Dns(x) => Some(Protocol::Dns(x)),
+            Protocol::Dnsaddr(x) => Some(Protocol::Dnsaddr(x)),
             _ => None,
         }
     }
@@ -79,47 +211,398 @@ impl SelectCorrectMultiAddrProtocol<Ipv6Addr> for Ipv6Addr {
     fn select_protocol<'a>(p: Protocol<'a>) -> Option<Protocol<'a>> {
         match p {
             Protocol::Ip6(x) => Some(Protocol::Ip6(x)),
+            Protocol::Dns6(x) => Some(Protocol::Dns6(x)),
+            Protocol::Dns(x) => Some(Protocol::Dns(x)),
+            Protocol::Dnsaddr(x) => Some(Protocol::Dnsaddr(x)),
             _ => None,
         }
     }
 }
-fn handle_ip4_ip6_question<T>(_question: &DnsQuestion, answer: String) -> HandleIp4Ip6Ret
+
+/// Parses a single multiaddr, `Ok(None)` means it is valid but of the other address family.
+fn parse_multiaddr_endpoint<T>(multiaddr: &str) -> Result<Option<MultiaddrEndpoint>, DnsError>
 where T: SelectCorrectMultiAddrProtocol<T> {
-    let mut response_packet: Vec<u8> = Vec::new();
-    let multiaddr_ip_query = answer.parse::<Multiaddr>().map_err(DnsError::from).and_then(|x: Multiaddr| -> Result<Protocol, DnsError> {
-        if x.len() < 2 {
-            return Err(DnsError::InvalidMultiaddr(None))
+    let x = multiaddr.parse::<Multiaddr>()?;
+    if x.len() < 2 {
+        return Err(DnsError::InvalidMultiaddr(None))
+    }
+    let mut protocols = x.iter();
+    let host = protocols.next().ok_or(DnsError::InvalidMultiaddr(None))?;
+    match host {
+        Protocol::Ip4(_) | Protocol::Ip6(_) | Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_) | Protocol::Dnsaddr(_) => {}
+        _ => return Err(DnsError::InvalidMultiaddr(None))
+    }
+    let Some(host) = T::select_protocol(host) else {
+        return Ok(None);
+    };
+
+    let mut endpoint = MultiaddrEndpoint { host: host.acquire(), tcp_port: None, udp_port: None };
+    for protocol in protocols {
+        match protocol {
+            Protocol::Tcp(port) => { endpoint.tcp_port.get_or_insert(port); }
+            Protocol::Udp(port) => { endpoint.udp_port.get_or_insert(port); }
+            _ => {}
         }
-        let v = x.into_iter().next().ok_or_else(|| DnsError::InvalidMultiaddr(None))?;
-        match T::select_protocol(v) {
-            Some(x) => Ok(x.acquire()),
-            None => Err(DnsError::InvalidMultiaddr(None))
+    }
+    Ok(Some(endpoint))
+}
+
+/// Parses every multiaddr of an ENS address record, entries are separated by whitespace or commas.
+fn parse_multiaddr_endpoints<T>(answer: &str) -> (Vec<MultiaddrEndpoint>, Vec<DnsError>)
+where T: SelectCorrectMultiAddrProtocol<T> {
+    let mut endpoints = Vec::new();
+    let mut errors = Vec::new();
+    for entry in answer.split(|c: char| c == ',' || c.is_whitespace()).filter(|x| !x.is_empty()) {
+        match parse_multiaddr_endpoint::<T>(entry) {
+            Ok(Some(endpoint)) => endpoints.push(endpoint),
+            Ok(None) => {}
+            Err(e) => errors.push(e),
         }
-    });
-    match multiaddr_ip_query {
-        Ok(Protocol::Ip4(ip)) => {
-            let rd_length:u16 = 4;
-            response_packet.extend_from_slice(&rd_length.to_be_bytes());
-            response_packet.extend_from_slice(&ip.octets());
-            HandleIp4Ip6Ret { answers: 1, response_packet }
-        },
-        Ok(Protocol::Ip6(ip)) => {
-            let rd_length:u16 = 16;
-            response_packet.extend_from_slice(&rd_length.to_be_bytes());
-            response_packet.extend_from_slice(&ip.octets());
-            HandleIp4Ip6Ret { answers: 1, response_packet }
+    }
+    (endpoints, errors)
+}
+
+fn handle_ip4_ip6_question<T>(question: &DnsQuestion, answer: String, shuffle: bool) -> HandleIp4Ip6Ret
+where T: SelectCorrectMultiAddrProtocol<T> {
+    let (endpoints, errors) = parse_multiaddr_endpoints::<T>(&answer);
+    let mut records: Vec<DnsRecord> = endpoints
+        .iter()
+        .filter_map(|x| match &x.host {
+            Protocol::Ip4(ip) => Some(answer_record(question, ip.octets().to_vec())),
+            Protocol::Ip6(ip) => Some(answer_record(question, ip.octets().to_vec())),
+            _ => None,
+        })
+        .collect();
+    if shuffle {
+        records.shuffle(&mut rand::thread_rng());
+    }
+    // a CNAME cannot coexist with other data, so addresses take precedence over hostnames
+    let cname_target = if records.is_empty() {
+        endpoints.iter().find_map(|x| x.hostname())
+    } else {
+        None
+    };
+    HandleIp4Ip6Ret { records, cname_target, errors }
+}
+
+/// TXT rdata is a sequence of character-strings of at most 255 bytes each.
+fn txt_rdata(answer: &str) -> Vec<u8> {
+    let mut rdata = Vec::new();
+    for chunk in answer.as_bytes().chunks(255) {
+        rdata.push(chunk.len() as u8);
+        rdata.extend_from_slice(chunk);
+    }
+    if rdata.is_empty() {
+        rdata.push(0);
+    }
+    rdata
+}
+
+fn answer_record(question: &DnsQuestion, rdata: Vec<u8>) -> DnsRecord {
+    DnsRecord {
+        name: question.qname.clone(),
+        rtype: question.qtype,
+        rclass: 1, // IN (Internet)
+        ttl: DEFAULT_TTL,
+        rdata,
+    }
+}
+
+/// Looks up the A and AAAA multiaddrs of the question's name.
+async fn lookup_endpoints<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+) -> Vec<MultiaddrEndpoint> {
+    let a_question = DnsQuestion { qtype: 1, ..question.clone() };
+    let aaaa_question = DnsQuestion { qtype: 28, ..question.clone() };
+    let (a, aaaa) = tokio::join!(
+        answer_provider.get_answer_async(a_question),
+        answer_provider.get_answer_async(aaaa_question)
+    );
+
+    let mut endpoints = a.map(|x| parse_multiaddr_endpoints::<Ipv4Addr>(&x).0).unwrap_or_default();
+    for endpoint in aaaa.map(|x| parse_multiaddr_endpoints::<Ipv6Addr>(&x).0).unwrap_or_default() {
+        if !endpoints.contains(&endpoint) {
+            endpoints.push(endpoint);
         }
-        _ => {
-            HandleIp4Ip6Ret { answers: 0, response_packet }
+    }
+    endpoints
+}
+
+/// Synthesizes an HTTPS/SVCB record from the name's multiaddrs.
+async fn answer_svcb_question<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+    config: &DnsConfig,
+) -> Vec<DnsRecord> {
+    let endpoints = lookup_endpoints(question, answer_provider).await;
+    let ipv4hint: Vec<Ipv4Addr> = endpoints
+        .iter()
+        .filter_map(|x| match x.host {
+            Protocol::Ip4(ip) => Some(ip),
+            _ => None,
+        })
+        .collect();
+    let ipv6hint: Vec<Ipv6Addr> = endpoints
+        .iter()
+        .filter_map(|x| match x.host {
+            Protocol::Ip6(ip) => Some(ip),
+            _ => None,
+        })
+        .collect();
+    let target = if ipv4hint.is_empty() && ipv6hint.is_empty() {
+        endpoints.iter().find_map(|x| x.hostname())
+    } else {
+        None
+    };
+    let port = endpoints.iter().find_map(|x| x.tcp_port).filter(|x| *x != 443);
+
+    SvcbRecord::service_mode(&config.https, target, port, ipv4hint, ipv6hint)
+        .map(|record| answer_record(question, record.serialize()))
+        .into_iter()
+        .collect()
+}
+
+/// Synthesizes SRV records for `_<service>._<tcp|udp>.<name>` from the ports of the name's multiaddrs.
+async fn answer_srv_question<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+) -> Vec<DnsRecord> {
+    let [service, transport, ..] = question.qname.labels.as_slice() else {
+        return vec![];
+    };
+    if !service.label.starts_with(b"_") {
+        return vec![];
+    }
+    let name = DnsName { labels: question.qname.labels[2..].to_vec() };
+    let host_question = DnsQuestion { qname: name.clone(), ..question.clone() };
+
+    let mut targets: Vec<(u16, DnsName)> = Vec::new();
+    for endpoint in lookup_endpoints(&host_question, answer_provider).await {
+        let port = match transport.label.to_ascii_lowercase().as_slice() {
+            b"_tcp" => endpoint.tcp_port,
+            b"_udp" => endpoint.udp_port,
+            _ => None,
+        };
+        if let Some(port) = port {
+            let target = (port, endpoint.hostname().unwrap_or_else(|| name.clone()));
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
         }
+    }
 
+    targets
+        .into_iter()
+        .map(|(port, target)| {
+            let mut rdata = Vec::new();
+            rdata.extend_from_slice(&0u16.to_be_bytes()); // priority
+            rdata.extend_from_slice(&0u16.to_be_bytes()); // weight
+            rdata.extend_from_slice(&port.to_be_bytes());
+            rdata.extend_from_slice(&target.serialize());
+            answer_record(question, rdata)
+        })
+        .collect()
+}
+
+/// Builds A/AAAA records from the multiaddrs of the question's name, hostnames are answered
+/// with a CNAME or flattened through the upstream.
+async fn lookup_address_records<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+    config: &DnsConfig,
+) -> Vec<DnsRecord> {
+    let Some(answer) = answer_provider.get_answer_async(question.clone()).await else {
+        return vec![];
+    };
+    let ret = if question.qtype == 1 {
+        handle_ip4_ip6_question::<Ipv4Addr>(question, answer, config.shuffle_address_records)
+    } else {
+        handle_ip4_ip6_question::<Ipv6Addr>(question, answer, config.shuffle_address_records)
+    };
+    for e in &ret.errors {
+        println!("error parsing multiaddr for {:?} {:?}", question.qname, e);
+    }
+    match (ret.cname_target, config.cname_flattening_upstream) {
+        (Some(target), Some(upstream)) => {
+            match upstream::resolve(upstream, &target, question.qtype).await {
+                Ok(rdatas) => rdatas.into_iter().map(|rdata| answer_record(question, rdata)).collect(),
+                Err(e) => {
+                    println!("error flattening {:?} to {:?} {:?}", question.qname, target, e);
+                    vec![]
+                }
+            }
+        }
+        (Some(target), None) => {
+            vec![DnsRecord { rtype: 5, ..answer_record(question, target.serialize()) }] // CNAME
+        }
+        (None, _) => ret.records,
+    }
+}
+
+/// Answers A/AAAA questions, an AAAA question without any IPv6 answer is synthesized from the
+/// A records through DNS64 when it is configured.
+async fn answer_address_question<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+    config: &DnsConfig,
+) -> Vec<DnsRecord> {
+    let records = lookup_address_records(question, answer_provider, config).await;
+    let Some(dns64) = config.dns64.as_ref().filter(|_| question.qtype == 28 && records.is_empty()) else {
+        return records;
+    };
+
+    let a_question = DnsQuestion { qtype: 1, ..question.clone() };
+    lookup_address_records(&a_question, answer_provider, config)
+        .await
+        .into_iter()
+        .filter(|x| x.rtype == 1)
+        .filter_map(|x| <[u8; 4]>::try_from(x.rdata.as_slice()).ok())
+        .filter_map(|x| dns64.synthesize(Ipv4Addr::from(x)))
+        .map(|x| answer_record(question, x.octets().to_vec()))
+        .collect()
+}
+
+async fn answer_question<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+    config: &DnsConfig,
+) -> Vec<DnsRecord> {
+    if question.qtype == 64 || question.qtype == 65 { //SVCB/HTTPS record
+        return answer_svcb_question(question, answer_provider, config).await;
+    }
+    if question.qtype == 33 { //SRV record
+        return answer_srv_question(question, answer_provider).await;
+    }
+    if question.qtype == 1 || question.qtype == 28 { //A/AAAA record
+        return answer_address_question(question, answer_provider, config).await;
+    }
+
+    let ans = answer_provider.get_answer_async(question.clone()).await;
+    println!("ans {:?}", ans);
+    match ans {
+        Some(answer) => {
+            if question.qtype == 16 {
+                vec![answer_record(question, txt_rdata(&answer))]
+            } else if question.qtype == 12 { //PTR record
+                match DnsName::punycode_encode(&answer) {
+                    Some(name) => vec![answer_record(question, name.serialize())],
+                    None => vec![],
+                }
+            } else if [keys::TLSA, keys::SSHFP, keys::OPENPGPKEY].contains(&question.qtype) {
+                match keys::parse_rdatas(question.qtype, &answer) {
+                    Ok(rdatas) => rdatas.into_iter().map(|rdata| answer_record(question, rdata)).collect(),
+                    Err(e) => {
+                        println!("error parsing key records for {:?} {:?}", question.qname, e);
+                        vec![]
+                    }
+                }
+            } else if question.qtype == caa::CAA {
+                match caa::parse_rdatas(&answer) {
+                    Ok(rdatas) => rdatas.into_iter().map(|rdata| answer_record(question, rdata)).collect(),
+                    Err(e) => {
+                        println!("error parsing caa records for {:?} {:?}", question.qname, e);
+                        vec![]
+                    }
+                }
+            } else {
+                vec![]
+            }
+        }
+        None => vec![],
     }
 }
 
-async fn generate_dns_response_packet<'a, P: DnsAnswerProvider>(
+const ANY: u16 = 255;
+
+/// Record types of a name fetched for a full ANY dump, names under `addr.reverse` only have PTR.
+const ANY_DUMP_QTYPES: &[u16] = &[1, 28, 65, 16, keys::SSHFP, keys::TLSA, keys::OPENPGPKEY, caa::CAA];
+
+/// Largest UDP response to a query without EDNS (RFC 1035), longer responses are truncated to it.
+const MAX_UDP_SIZE: usize = 512;
+
+/// Size of the fixed DNS header.
+const HEADER_SIZE: usize = 12;
+
+/// Drops the records not fitting in a UDP response after `used` bytes, returns whether any were dropped.
+fn truncate_to_fit(records: &mut Vec<DnsRecord>, used: usize) -> bool {
+    let mut size = used;
+    let fits = records.iter().take_while(|x| {
+        size += x.serialize().len();
+        size <= MAX_UDP_SIZE
+    }).count();
+    let truncated = fits < records.len();
+    records.truncate(fits);
+    truncated
+}
+
+/// Answers ANY per RFC 8482 with a synthesized HINFO instead of every record of the name,
+/// unless `full_dump` is set for a trusted client.
+async fn answer_any_question<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    answer_provider: &P,
+    config: &DnsConfig,
+    full_dump: bool,
+) -> Vec<DnsRecord> {
+    if !full_dump {
+        let rdata = [txt_rdata("RFC8482"), txt_rdata("")].concat();
+        return vec![DnsRecord { rtype: 13, ..answer_record(question, rdata) }]; // HINFO
+    }
+
+    let qtypes = match question.qname.is_subdomain_of(&DnsName::from("addr.reverse".to_string())) {
+        true => &[12][..],
+        false => ANY_DUMP_QTYPES,
+    };
+    let answers = join_all(qtypes.iter().map(|qtype| async move {
+        let question = DnsQuestion { qtype: *qtype, ..question.clone() };
+        answer_question(&question, answer_provider, config).await
+    }))
+    .await;
+    let mut records: Vec<DnsRecord> = Vec::new();
+    for record in answers.into_iter().flatten() {
+        if !records.contains(&record) {
+            records.push(record);
+        }
+    }
+    records
+}
+
+fn with_ttl(records: Vec<DnsRecord>, ttl: u32) -> Vec<DnsRecord> {
+    records.into_iter().map(|x| DnsRecord { ttl, ..x }).collect()
+}
+
+/// Answers SOA and NS questions for the apex of the served zone.
+async fn answer_apex_question<P: DnsAnswerProvider>(
+    question: &DnsQuestion,
+    zone: &ZoneConfig,
+    answer_provider: &P,
+) -> Vec<DnsRecord> {
+    match question.qtype {
+        2 => zone.ns_rdatas().into_iter().map(|rdata| answer_record(question, rdata)).collect(),
+        6 => vec![soa_record(zone, answer_provider).await],
+        _ => vec![],
+    }
+}
+
+/// SOA record of the served zone, the serial is derived from the current block number. Its TTL
+/// is the SOA MINIMUM so negative answers are cached for `min(TTL, MINIMUM)` (RFC 2308).
+async fn soa_record<P: DnsAnswerProvider>(zone: &ZoneConfig, answer_provider: &P) -> DnsRecord {
+    let serial = answer_provider.get_zone_serial_async().await.unwrap_or(1);
+    DnsRecord {
+        name: zone.apex.clone(),
+        rtype: 6, // SOA
+        rclass: 1, // IN (Internet)
+        ttl: zone.minimum,
+        rdata: zone.soa_rdata(serial),
+    }
+}
+
+async fn generate_dns_response_packet<P: DnsAnswerProvider>(
     questions: Vec<DnsQuestion>,
     original_header: DnsHeader,
-    answer_provider: &'a P,
+    client: IpAddr,
+    answer_provider: &P,
+    config: &DnsConfig,
 ) -> Vec<u8> {
     let flags = DnsFlags {
         qr: true,
@@ -143,49 +626,46 @@ async fn generate_dns_response_packet<'a, P: DnsAnswerProvider>(
 
     // Serialize questions
     for question in &questions {
-        let serialized_question = serialize_dns_question(&question);
+        let serialized_question = serialize_dns_question(question);
         response_packet.extend_from_slice(&serialized_question);
     }
 
+    let mut authority: Vec<DnsRecord> = Vec::new();
     for question in &questions {
-        let ans = answer_provider.get_answer_async(question.clone()).await;
-        println!("ans {:?}", ans);
-        if let Some(answer) = ans {
-            let qname_bytes = DnsName::serialize(&question.qname);
-            let qclass:u16 = 1; // IN (Internet)
-            let ttl:u32 = 300; //TODO
-            if question.qtype == 16 {
-                header.an_count += 1;
-                let qtype: u16 = 16; // TXT
-                let txt_data = answer.as_bytes();
-                let rd_length: u16 = (txt_data.len() + 1) as u16; // +1 for the TXT length byte
-                response_packet.extend_from_slice(&qname_bytes);
-                response_packet.extend_from_slice(&qtype.to_be_bytes());
-                response_packet.extend_from_slice(&qclass.to_be_bytes());
-                response_packet.extend_from_slice(&ttl.to_be_bytes());
-                response_packet.extend_from_slice(&rd_length.to_be_bytes());
-                response_packet.push(txt_data.len() as u8);
-                response_packet.extend_from_slice(txt_data);
+        let zone = config.zone.as_ref().filter(|zone| zone.contains(&question.qname));
+        let full_dump = question.qtype == ANY && config.any_full_dump_clients.iter().any(|x| x.contains(&client));
+        let mut records = match zone {
+            Some(zone) if zone.is_apex(&question.qname) && (question.qtype == 2 || question.qtype == 6) => {
+                answer_apex_question(question, zone, answer_provider).await
             }
-            else if question.qtype == 1 { //A record
-                response_packet.extend_from_slice(&qname_bytes);
-                response_packet.extend_from_slice(&question.qtype.to_be_bytes());
-                response_packet.extend_from_slice(&qclass.to_be_bytes());
-                response_packet.extend_from_slice(&ttl.to_be_bytes());
-                let ret = handle_ip4_ip6_question::<Ipv4Addr>(question, answer);
-                header.an_count += ret.answers;
-                response_packet.extend_from_slice(&ret.response_packet);
-            } else if question.qtype == 28 { //AAAA record
-                response_packet.extend_from_slice(&qname_bytes);
-                response_packet.extend_from_slice(&question.qtype.to_be_bytes());
-                response_packet.extend_from_slice(&qclass.to_be_bytes());
-                response_packet.extend_from_slice(&ttl.to_be_bytes());
-                let ret = handle_ip4_ip6_question::<Ipv6Addr>(question, answer);
-                header.an_count += ret.answers;
-                response_packet.extend_from_slice(&ret.response_packet);
+            _ if question.qtype == ANY => {
+                with_ttl(answer_any_question(question, answer_provider, config, full_dump).await, config.ttl)
+            }
+            _ => with_ttl(answer_question(question, answer_provider, config).await, config.ttl),
+        };
+
+        if let Some(zone) = zone {
+            header.flags.aa = true;
+            if records.is_empty() {
+                if !zone.is_apex(&question.qname) && !answer_provider.name_exists_async(&question.qname).await {
+                    header.flags.rcode = RCode::NameError;
+                }
+                authority.push(soa_record(zone, answer_provider).await);
             }
         }
+
+        if truncate_to_fit(&mut records, HEADER_SIZE + response_packet.len()) {
+            header.flags.tc = true;
+        }
+        header.an_count += records.len() as u16;
+        records.iter().for_each(|x| response_packet.extend_from_slice(&x.serialize()));
+    }
+
+    if truncate_to_fit(&mut authority, HEADER_SIZE + response_packet.len()) {
+        header.flags.tc = true;
     }
+    header.ns_count = authority.len() as u16;
+    authority.iter().for_each(|x| response_packet.extend_from_slice(&x.serialize()));
 
     let serialized_header = DnsHeader::serialize(&header);
     response_packet.splice(0..0, serialized_header.iter().cloned());
@@ -194,7 +674,7 @@ async fn generate_dns_response_packet<'a, P: DnsAnswerProvider>(
 }
 
 
-pub async fn handle_dns_packet<P: DnsAnswerProvider>(data: Vec<u8>, answer_provider: &P) -> Vec<u8> {
+pub async fn handle_dns_packet<P: DnsAnswerProvider>(data: Vec<u8>, client: IpAddr, answer_provider: &P, config: &DnsConfig) -> Vec<u8> {
     match DnsHeader::parse(&data) {
         Ok((remaining_data, header)) => {
             println!("Parsed header: {:?}", header);
@@ -215,7 +695,7 @@ pub async fn handle_dns_packet<P: DnsAnswerProvider>(data: Vec<u8>, answer_provi
             } else {
                 vec![]
             };
-            generate_dns_response_packet(questions, header, answer_provider).await
+            generate_dns_response_packet(questions, header, client, answer_provider, config).await
         }
         Err(err) => {
             println!("Failed to parse header: {:?}", err);
@@ -231,17 +711,82 @@ mod tests {
     use super::*;
     use async_trait::async_trait;
 
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
     struct DummyAnswerProvider {
         _lifetime: PhantomData<()>
     }
 
     #[async_trait]
-    impl<'a> DnsAnswerProvider for DummyAnswerProvider {
+    impl DnsAnswerProvider for DummyAnswerProvider {
         async fn get_answer_async(&self, _question: DnsQuestion) -> Option<String> {
             Some("dummy_answer".into())
         }
     }
 
+    struct StaticAnswerProvider;
+
+    #[async_trait]
+    impl DnsAnswerProvider for StaticAnswerProvider {
+        async fn get_answer_async(&self, question: DnsQuestion) -> Option<String> {
+            match question.qtype {
+                1 => Some("/ip4/1.2.3.4/tcp/443".into()),
+                12 => Some("bücher.eth".into()),
+                257 => Some("0 issue \"letsencrypt.org\"\n0 iodef \"mailto:security@example.com\"".into()),
+                _ => None,
+            }
+        }
+    }
+
+    struct HostnameAnswerProvider;
+
+    #[async_trait]
+    impl DnsAnswerProvider for HostnameAnswerProvider {
+        async fn get_answer_async(&self, question: DnsQuestion) -> Option<String> {
+            match question.qtype {
+                1 => Some("/dns4/host.example/tcp/8443".into()),
+                _ => None,
+            }
+        }
+    }
+
+    struct NxDomainAnswerProvider;
+
+    #[async_trait]
+    impl DnsAnswerProvider for NxDomainAnswerProvider {
+        async fn get_answer_async(&self, _question: DnsQuestion) -> Option<String> {
+            None
+        }
+        async fn name_exists_async(&self, _qname: &DnsName) -> bool {
+            false
+        }
+        async fn get_zone_serial_async(&self) -> Option<u32> {
+            Some(42)
+        }
+    }
+
+    fn query_header(qd_count: u16) -> DnsHeader {
+        DnsHeader {
+            id: 1,
+            flags: DnsFlags { qr: false, opcode: Opcode::Query, aa: false, tc: false, rd: false, ra: false, rcode: RCode::NoError },
+            qd_count,
+            an_count: 0,
+            ar_count: 0,
+            ns_count: 0,
+        }
+    }
+
+    fn eth_zone_config() -> DnsConfig {
+        DnsConfig {
+            zone: Some(ZoneConfig::new(
+                DnsName::from("eth".to_string()),
+                vec![DnsName::from("ns1.example.com".to_string()), DnsName::from("ns2.example.com".to_string())],
+                DnsName::from("hostmaster.example.com".to_string()),
+            )),
+            ..Default::default()
+        }
+    }
+
     #[tokio::test]
     async fn test_dnsheader_serialize_idempotent() {
         let header = DnsHeader {
@@ -287,7 +832,9 @@ mod tests {
                 ar_count: 0,
                 ns_count: 0,
             },
+            CLIENT,
             &answer_provider,
+            &DnsConfig::default(),
         )
         .await;
 
@@ -313,11 +860,355 @@ mod tests {
                 ar_count: 0,
                 ns_count: 0,
             },
+            CLIENT,
             &answer_provider,
+            &DnsConfig::default(),
         )
         .await;
         // Header + serialized question + serialized answer
         assert!(packet.len() > 12);
     }
 
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_https_question() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("example.eth".to_string()),
+            qtype: 65, // HTTPS Record
+            qclass: 1, // IN (Internet)
+        }];
+        let config = DnsConfig {
+            https: HttpsConfig {
+                alpn: vec!["h2".to_string()],
+                ipv6hint: vec![Ipv6Addr::LOCALHOST],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let packet = generate_dns_response_packet(
+            questions.clone(),
+            DnsHeader {
+                id: 1,
+                flags: DnsFlags { qr: false, opcode: Opcode::Query, aa: false, tc: false, rd: false, ra: false, rcode: RCode::NoError },
+                qd_count: 1,
+                an_count: 0,
+                ar_count: 0,
+                ns_count: 0,
+            },
+            CLIENT,
+            &StaticAnswerProvider,
+            &config,
+        )
+        .await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rtype, 65);
+        let expected = SvcbRecord {
+            priority: 1,
+            target: DnsName { labels: vec![] },
+            params: vec![
+                svcb::SvcParam::Alpn(vec!["h2".to_string()]),
+                svcb::SvcParam::Ipv4Hint(vec![Ipv4Addr::new(1, 2, 3, 4)]),
+                svcb::SvcParam::Ipv6Hint(vec![Ipv6Addr::LOCALHOST]),
+            ],
+        };
+        assert_eq!(record.rdata, expected.serialize());
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_nxdomain_in_zone() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("missing.eth".to_string()),
+            qtype: 16, // TXT Record
+            qclass: 1, // IN (Internet)
+        }];
+        let config = eth_zone_config();
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &NxDomainAnswerProvider, &config).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert!(header.flags.aa);
+        assert_eq!(header.flags.rcode, RCode::NameError);
+        assert_eq!(header.an_count, 0);
+        assert_eq!(header.ns_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.name, DnsName::from("eth".to_string()));
+        assert_eq!(record.rtype, 6);
+        assert_eq!(record.rdata, config.zone.unwrap().soa_rdata(42));
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_caa() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("vitalik.eth".to_string()),
+            qtype: caa::CAA,
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions.clone(), query_header(1), CLIENT, &StaticAnswerProvider, &eth_zone_config()).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 2);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rtype, caa::CAA);
+        assert_eq!(record.rdata, b"\x00\x05issueletsencrypt.org".to_vec());
+
+        // nothing published is an explicit NODATA answer, not an error
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &HostnameAnswerProvider, &eth_zone_config()).await;
+        let (_, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.flags.rcode, RCode::NoError);
+        assert_eq!(header.an_count, 0);
+        assert_eq!(header.ns_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_any() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("vitalik.eth".to_string()),
+            qtype: ANY,
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions.clone(), query_header(1), CLIENT, &StaticAnswerProvider, &DnsConfig::default()).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rtype, 13);
+        assert_eq!(record.rdata, b"\x07RFC8482\x00".to_vec());
+
+        let config = DnsConfig { any_full_dump_clients: vec!["127.0.0.0/8".parse().unwrap()], ..Default::default() };
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &StaticAnswerProvider, &config).await;
+        let (mut input, header) = DnsHeader::parse(&packet).unwrap();
+        (input, _) = DnsQuestion::parse(input).unwrap();
+        let mut rtypes = Vec::new();
+        for _ in 0..header.an_count {
+            let (rest, record) = DnsRecord::parse(input).unwrap();
+            rtypes.push(record.rtype);
+            input = rest;
+        }
+        // A, HTTPS synthesized from it and the two CAA records
+        assert_eq!(rtypes, vec![1, 65, caa::CAA, caa::CAA]);
+        assert!(!header.flags.tc);
+
+        // reverse names only have PTR
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("d8da6bf26964af9d7eed9e03e53415d37aa96045.addr.reverse".to_string()),
+            qtype: ANY,
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &StaticAnswerProvider, &config).await;
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rtype, 12);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_truncated() {
+        struct LongTxtAnswerProvider;
+
+        #[async_trait]
+        impl DnsAnswerProvider for LongTxtAnswerProvider {
+            async fn get_answer_async(&self, question: DnsQuestion) -> Option<String> {
+                match question.qtype {
+                    1 => Some("/ip4/1.2.3.4/tcp/443".into()),
+                    16 => Some("x".repeat(600)),
+                    _ => None,
+                }
+            }
+        }
+
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("vitalik.eth".to_string()),
+            qtype: ANY,
+            qclass: 1, // IN (Internet)
+        }];
+        let config = DnsConfig { any_full_dump_clients: vec!["127.0.0.0/8".parse().unwrap()], ..Default::default() };
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &LongTxtAnswerProvider, &config).await;
+        let (_, header) = DnsHeader::parse(&packet).unwrap();
+        assert!(packet.len() <= MAX_UDP_SIZE);
+        assert!(header.flags.tc);
+        // A and HTTPS fit, the TXT record does not
+        assert_eq!(header.an_count, 2);
+
+        // so does any other answer longer than a UDP response
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("vitalik.eth".to_string()),
+            qtype: 16, // TXT Record
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions, query_header(2), CLIENT, &LongTxtAnswerProvider, &config).await;
+        let (_, header) = DnsHeader::parse(&packet).unwrap();
+        assert!(packet.len() <= MAX_UDP_SIZE);
+        assert!(header.flags.tc);
+        assert_eq!(header.an_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_ttl() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("vitalik.eth".to_string()),
+            qtype: 1, // A Record
+            qclass: 1, // IN (Internet)
+        }];
+        let config = DnsConfig { ttl: 30, ..Default::default() };
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &StaticAnswerProvider, &config).await;
+        let (input, _) = DnsHeader::parse(&packet).unwrap();
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.ttl, 30);
+
+        // the zone's own records keep their TTLs
+        let config = DnsConfig { ttl: 30, ..eth_zone_config() };
+        let questions = vec![
+            DnsQuestion { qname: DnsName::from("eth".to_string()), qtype: 6, qclass: 1 },
+            DnsQuestion { qname: DnsName::from("eth".to_string()), qtype: 2, qclass: 1 },
+        ];
+        let packet = generate_dns_response_packet(questions, query_header(2), CLIENT, &NxDomainAnswerProvider, &config).await;
+        let (mut input, header) = DnsHeader::parse(&packet).unwrap();
+        for _ in 0..header.qd_count {
+            input = DnsQuestion::parse(input).unwrap().0;
+        }
+        let (input, soa) = DnsRecord::parse(input).unwrap();
+        let (_, ns) = DnsRecord::parse(input).unwrap();
+        assert_eq!(soa.ttl, 300);
+        assert_eq!(ns.ttl, DEFAULT_TTL);
+
+        // negative answers are cached for the SOA MINIMUM
+        let questions = vec![DnsQuestion { qname: DnsName::from("missing.eth".to_string()), qtype: 16, qclass: 1 }];
+        let packet = generate_dns_response_packet(questions, query_header(3), CLIENT, &NxDomainAnswerProvider, &config).await;
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.flags.rcode, RCode::NameError);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, soa) = DnsRecord::parse(input).unwrap();
+        assert_eq!(soa.ttl, config.zone.unwrap().minimum);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_dns64() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("vitalik.eth".to_string()),
+            qtype: 28, // AAAA Record
+            qclass: 1, // IN (Internet)
+        }];
+        let config = DnsConfig {
+            dns64: Some(Dns64Config { prefix: "64:ff9b::/96".parse().unwrap(), exclude: vec![] }),
+            ..Default::default()
+        };
+        let packet = generate_dns_response_packet(questions.clone(), query_header(1), CLIENT, &StaticAnswerProvider, &config).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rtype, 28);
+        assert_eq!(record.rdata, "64:ff9b::102:304".parse::<Ipv6Addr>().unwrap().octets().to_vec());
+
+        let config = DnsConfig {
+            dns64: Some(Dns64Config { prefix: "64:ff9b::/96".parse().unwrap(), exclude: vec!["1.0.0.0/8".parse().unwrap()] }),
+            ..Default::default()
+        };
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &StaticAnswerProvider, &config).await;
+        let (_, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_apex_ns() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("eth".to_string()),
+            qtype: 2, // NS Record
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &NxDomainAnswerProvider, &eth_zone_config()).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert!(header.flags.aa);
+        assert_eq!(header.flags.rcode, RCode::NoError);
+        assert_eq!(header.an_count, 2);
+        assert_eq!(header.ns_count, 0);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rdata, DnsName::from("ns1.example.com".to_string()).serialize());
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_ptr_is_punycode_encoded() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("d8da6bf26964af9d7eed9e03e53415d37aa96045.addr.reverse".to_string()),
+            qtype: 12, // PTR Record
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &StaticAnswerProvider, &DnsConfig::default()).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rdata, DnsName::from("xn--bcher-kva.eth".to_string()).serialize());
+    }
+
+    #[test]
+    fn test_handle_ip4_ip6_question_multiple_multiaddrs() {
+        let question = DnsQuestion {
+            qname: DnsName::from("example.eth".to_string()),
+            qtype: 1, // A Record
+            qclass: 1, // IN (Internet)
+        };
+        let answer = "/ip4/1.2.3.4/tcp/443, /ip6/::1/tcp/443\n/ip4/5.6.7.8/tcp/80 not-a-multiaddr";
+        let ret = handle_ip4_ip6_question::<Ipv4Addr>(&question, answer.to_string(), false);
+        assert_eq!(
+            ret.records.iter().map(|x| x.rdata.clone()).collect::<Vec<Vec<u8>>>(),
+            vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8]]
+        );
+        assert_eq!(ret.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_dns4_multiaddr_is_cname() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("example.eth".to_string()),
+            qtype: 1, // A Record
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &HostnameAnswerProvider, &DnsConfig::default()).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        assert_eq!(record.rtype, 5);
+        assert_eq!(record.rdata, DnsName::from("host.example".to_string()).serialize());
+    }
+
+    #[tokio::test]
+    async fn test_generate_dns_response_packet_srv_from_multiaddr_port() {
+        let questions = vec![DnsQuestion {
+            qname: DnsName::from("_https._tcp.example.eth".to_string()),
+            qtype: 33, // SRV Record
+            qclass: 1, // IN (Internet)
+        }];
+        let packet = generate_dns_response_packet(questions, query_header(1), CLIENT, &HostnameAnswerProvider, &DnsConfig::default()).await;
+
+        let (input, header) = DnsHeader::parse(&packet).unwrap();
+        assert_eq!(header.an_count, 1);
+        let (input, _) = DnsQuestion::parse(input).unwrap();
+        let (_, record) = DnsRecord::parse(input).unwrap();
+        let mut expected = vec![0, 0, 0, 0, 0x20, 0xfb];
+        expected.extend_from_slice(&DnsName::from("host.example".to_string()).serialize());
+        assert_eq!(record.rdata, expected);
+    }
+
+    #[test]
+    fn test_txt_rdata_splits_long_strings() {
+        let answer = "a".repeat(300);
+        let rdata = txt_rdata(&answer);
+        assert_eq!(rdata.len(), 302);
+        assert_eq!(rdata[0], 255);
+        assert_eq!(rdata[256], 45);
+    }
 }
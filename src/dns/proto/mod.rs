@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use nom::{IResult, number::complete::{be_u16, be_u8}, bytes::complete::take};
+use nom::{IResult, number::complete::{be_u16, be_u32, be_u8}, bytes::complete::take};
 
 pub use self::parseable::Parseable;
 
@@ -27,10 +27,10 @@ pub struct DnsHeader {
 }
 
 
-impl<'a> Parseable<DnsHeader> for DnsHeader {
+impl Parseable<DnsHeader> for DnsHeader {
     fn parse(input: &[u8]) -> IResult<&[u8], DnsHeader> {
         let (input, id) = be_u16(input)?;
-        let (input, flags) = DnsFlags::parse(input.into())?;
+        let (input, flags) = DnsFlags::parse(input)?;
         let (input, qd_count) = be_u16(input)?;
         let (input, an_count) = be_u16(input)?;
         let (input, ns_count) = be_u16(input)?;
@@ -53,7 +53,8 @@ impl<'a> Parseable<DnsHeader> for DnsHeader {
         header.extend_from_slice(&self.flags.serialize());
         header.extend_from_slice(&self.qd_count.to_be_bytes());
         header.extend_from_slice(&self.an_count.to_be_bytes());
-        header.extend_from_slice(&[0u8; 4]);
+        header.extend_from_slice(&self.ns_count.to_be_bytes());
+        header.extend_from_slice(&self.ar_count.to_be_bytes());
         header
     }
 }
@@ -118,6 +119,7 @@ pub enum RCode {
     NoError = 0,
     FormatError = 1,
     ServerFailure = 2,
+    NameError = 3,
     Other(u16),
 }
 
@@ -128,6 +130,7 @@ impl From<u16> for RCode {
             0 => RCode::NoError,
             1 => RCode::FormatError,
             2 => RCode::ServerFailure,
+            3 => RCode::NameError,
             _ => RCode::Other(code),
         }
     }
@@ -138,6 +141,7 @@ impl From<RCode> for u16 {
             RCode::NoError => 0,
             RCode::FormatError => 1,
             RCode::ServerFailure => 2,
+            RCode::NameError => 3,
             RCode::Other(code) => code,
         }
     }
@@ -154,14 +158,29 @@ impl From<String> for DnsLabel {
     }
 }
 
-impl<'a> DnsLabel {
+impl DnsLabel {
+    /// Decodes the label into its unicode form, ASCII letters are lowercased as DNS names are
+    /// case-insensitive and ENS names are normalized to lowercase.
     pub fn punycode_decode(&self) -> Option<String> {
-        if self.label.len() >= 4 && &self.label[0..4] == b"xn--" {
-            let decoded = punycode::decode(String::from_utf8(self.label[4..].to_vec()).ok()?.as_str()).ok()?;
+        let label = self.label.to_ascii_lowercase();
+        if label.len() >= 4 && &label[0..4] == b"xn--" {
+            let decoded = punycode::decode(String::from_utf8(label[4..].to_vec()).ok()?.as_str()).ok()?;
             println!("{:?}", decoded);
             Some(decoded)
         } else {
-            String::from_utf8(self.label.to_vec()).ok()
+            String::from_utf8(label).ok()
+        }
+    }
+}
+
+impl DnsLabel {
+    /// Encodes a unicode label into its `xn--` form, ASCII labels are kept as is.
+    pub fn punycode_encode(label: &str) -> Option<DnsLabel> {
+        if label.is_ascii() {
+            Some(DnsLabel::from(label.to_string()))
+        } else {
+            let encoded = punycode::encode(label).ok()?;
+            Some(DnsLabel::from(format!("xn--{}", encoded)))
         }
     }
 }
@@ -192,11 +211,11 @@ impl From<String> for DnsName {
             .split(".")
             .map(|label| DnsLabel::from(label.to_string()))
             .collect::<Vec<DnsLabel>>();
-        DnsName { labels: labels }
+        DnsName { labels }
     }
 }
 
-impl<'a> DnsName {
+impl DnsName {
     pub fn is_label_of(&self, other: &DnsName) -> bool {
         if self.labels.len() > other.labels.len() {
             return false;
@@ -217,6 +236,18 @@ impl<'a> DnsName {
         }
     }
 
+    /// Case insensitive check that `self` is `zone` or a name below it.
+    pub fn is_subdomain_of(&self, zone: &DnsName) -> bool {
+        if zone.labels.len() > self.labels.len() {
+            return false;
+        }
+        self.labels
+            .iter()
+            .rev()
+            .zip(zone.labels.iter().rev())
+            .all(|(a, b)| a.label.eq_ignore_ascii_case(&b.label))
+    }
+
     pub fn remove_prefix_labels(&self, prefix: &DnsName) -> Option<DnsName> {
         if !prefix.is_label_of(self) {
             println!("not label of");
@@ -242,6 +273,14 @@ impl<'a> DnsName {
         Some(DnsName { labels: new_labels })
     }
 
+    pub fn punycode_encode(name: &str) -> Option<DnsName> {
+        let labels = name
+            .split('.')
+            .map(DnsLabel::punycode_encode)
+            .collect::<Option<Vec<DnsLabel>>>()?;
+        Some(DnsName { labels })
+    }
+
     pub fn punycode_decode(&self) -> Option<String> {
         let mut decoded_labels = Vec::new();
         for label in &self.labels {
@@ -253,14 +292,14 @@ impl<'a> DnsName {
 }
 
 
-impl<'a> Parseable<DnsName> for DnsName {
+impl Parseable<DnsName> for DnsName {
     fn parse(input: &[u8]) -> IResult<&[u8], DnsName> {
         let mut labels = Vec::new();
         let mut remaining_input = input;
         loop {
             println!("{:?}", remaining_input);
             let (input, label) = DnsLabel::parse(remaining_input)?;
-            remaining_input = &input;
+            remaining_input = input;
             if label.label.is_empty() {
                 break;
             }
@@ -285,7 +324,7 @@ pub struct DnsQuestion {
     pub qclass: u16,
 }
 
-impl<'a> Parseable<DnsQuestion> for DnsQuestion {
+impl Parseable<DnsQuestion> for DnsQuestion {
     fn parse(input: &[u8]) -> IResult<&[u8], DnsQuestion> {
         let (input, qname) = DnsName::parse(input)?;
         let (input, qtype) = be_u16(input)?;
@@ -307,7 +346,56 @@ impl<'a> Parseable<DnsQuestion> for DnsQuestion {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DnsRecord {
+    pub name: DnsName,
+    pub rtype: u16,
+    pub rclass: u16,
+    pub ttl: u32,
+    pub rdata: Vec<u8>,
+}
+
+impl Parseable<DnsRecord> for DnsRecord {
+    fn parse(input: &[u8]) -> IResult<&[u8], DnsRecord> {
+        let (input, name) = DnsName::parse(input)?;
+        let (input, rtype) = be_u16(input)?;
+        let (input, rclass) = be_u16(input)?;
+        let (input, ttl) = be_u32(input)?;
+        let (input, rd_length) = be_u16(input)?;
+        let (input, rdata) = take(rd_length)(input)?;
+        Ok((
+            input,
+            DnsRecord {
+                name,
+                rtype,
+                rclass,
+                ttl,
+                rdata: rdata.to_vec(),
+            },
+        ))
+    }
+    fn serialize(&self) -> Vec<u8> {
+        let mut serialized = DnsName::serialize(&self.name);
+        serialized.extend_from_slice(&self.rtype.to_be_bytes());
+        serialized.extend_from_slice(&self.rclass.to_be_bytes());
+        serialized.extend_from_slice(&self.ttl.to_be_bytes());
+        serialized.extend_from_slice(&(self.rdata.len() as u16).to_be_bytes());
+        serialized.extend_from_slice(&self.rdata);
+        serialized
+    }
+}
+
 #[async_trait]
 pub trait DnsAnswerProvider: Send + Sync {
     async fn get_answer_async(&self, question: DnsQuestion) -> Option<String>;
+
+    /// Whether the name exists at all, this tells NXDOMAIN apart from NODATA.
+    async fn name_exists_async(&self, _qname: &DnsName) -> bool {
+        true
+    }
+
+    /// Serial number for the SOA record of the served zone.
+    async fn get_zone_serial_async(&self) -> Option<u32> {
+        None
+    }
 }
\ No newline at end of file
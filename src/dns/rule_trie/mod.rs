@@ -15,7 +15,7 @@ pub struct RuleTrieKeyString {
     keys: Vec<RuleTrieKey>,
 }
 
-impl<'a> From<String> for RuleTrieKeyString {
+impl From<String> for RuleTrieKeyString {
     fn from(string: String) -> Self {
         let keys = string.split('.').map(|x| (false, x)).map(|(stop_processing_wildcards, x)| {
             
@@ -35,7 +35,7 @@ impl<'a> From<String> for RuleTrieKeyString {
 impl RuleTrieKeyString {
     #[allow(dead_code)]
     pub fn left_pop_clone(&self) -> Option<(RuleTrieKey, RuleTrieKeyString)> {
-        if self.keys.len() == 0 {
+        if self.keys.is_empty() {
             return None;
         }
         let mut keys = self.keys.clone();
@@ -55,7 +55,7 @@ enum RuleTrieNode<T> {
 }
 
 #[allow(dead_code)]
-impl<'a, T> RuleTrieNode<T> {
+impl<T> RuleTrieNode<T> {
     pub fn is_continue(&self) -> bool {
         match self {
             RuleTrieNode::Continue(_) => true,
@@ -116,10 +116,10 @@ impl<T> RuleTrie<T> where T: std::fmt::Debug {
                         match node {
                             RuleTrieNode::Continue(trie) => trie.insert(keyfrag, value),
                             RuleTrieNode::Elem(_) => {
-                                return Err("Cannot insert into trie, key already exists".into())
+                                Err("Cannot insert into trie, key already exists".into())
                             }
                             RuleTrieNode::None => {
-                                return Err("Cannot insert into trie, key already exists".into())
+                                Err("Cannot insert into trie, key already exists".into())
                             }
                         }
                     }
@@ -139,7 +139,7 @@ impl<T> RuleTrie<T> where T: std::fmt::Debug {
 
             }
             None => {
-                return Ok(())
+                Ok(())
             }
         }
     }
@@ -0,0 +1,209 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::proto::{DnsName, Parseable};
+
+/// Static settings for synthesized HTTPS/SVCB records (RFC 9460).
+///
+/// The address hints are gateway defaults, they are only used for an address
+/// family when the ENS name does not publish a multiaddr for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HttpsConfig {
+    pub alpn: Vec<String>,
+    pub port: Option<u16>,
+    pub ech: Option<Vec<u8>>,
+    pub ipv4hint: Vec<Ipv4Addr>,
+    pub ipv6hint: Vec<Ipv6Addr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    Alpn(Vec<String>),
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+}
+
+impl SvcParam {
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Alpn(_) => 1,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+        }
+    }
+
+    fn serialize_value(&self) -> Vec<u8> {
+        let mut value = Vec::new();
+        match self {
+            SvcParam::Alpn(ids) => {
+                for id in ids {
+                    value.push(id.len() as u8);
+                    value.extend_from_slice(id.as_bytes());
+                }
+            }
+            SvcParam::Port(port) => value.extend_from_slice(&port.to_be_bytes()),
+            SvcParam::Ipv4Hint(ips) => ips.iter().for_each(|ip| value.extend_from_slice(&ip.octets())),
+            SvcParam::Ech(config) => value.extend_from_slice(config),
+            SvcParam::Ipv6Hint(ips) => ips.iter().for_each(|ip| value.extend_from_slice(&ip.octets())),
+        }
+        value
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvcbRecord {
+    pub priority: u16,
+    pub target: DnsName,
+    pub params: Vec<SvcParam>,
+}
+
+impl SvcbRecord {
+    /// Builds a ServiceMode record pointing at `target`, or at the owner name itself (".")
+    /// when there is none. A port taken from the name's multiaddrs overrides the configured one.
+    /// The ECH config is the gateway's, it is only advertised when every hint is a gateway address.
+    pub fn service_mode(
+        config: &HttpsConfig,
+        target: Option<DnsName>,
+        port: Option<u16>,
+        ipv4hint: Vec<Ipv4Addr>,
+        ipv6hint: Vec<Ipv6Addr>,
+    ) -> Option<SvcbRecord> {
+        let (target, ipv4hint, ipv6hint, gateway) = match target {
+            // the gateway defaults are not hints for some other host
+            Some(target) => (target, ipv4hint, ipv6hint, false),
+            None => {
+                let gateway = ipv4hint.is_empty() && ipv6hint.is_empty();
+                let ipv4hint = if ipv4hint.is_empty() { config.ipv4hint.clone() } else { ipv4hint };
+                let ipv6hint = if ipv6hint.is_empty() { config.ipv6hint.clone() } else { ipv6hint };
+                if ipv4hint.is_empty() && ipv6hint.is_empty() {
+                    return None;
+                }
+                (DnsName { labels: vec![] }, ipv4hint, ipv6hint, gateway)
+            }
+        };
+
+        let mut params = Vec::new();
+        if !config.alpn.is_empty() {
+            params.push(SvcParam::Alpn(config.alpn.clone()));
+        }
+        if let Some(port) = port.or(config.port) {
+            params.push(SvcParam::Port(port));
+        }
+        if !ipv4hint.is_empty() {
+            params.push(SvcParam::Ipv4Hint(ipv4hint));
+        }
+        if let Some(ech) = config.ech.as_ref().filter(|_| gateway) {
+            params.push(SvcParam::Ech(ech.clone()));
+        }
+        if !ipv6hint.is_empty() {
+            params.push(SvcParam::Ipv6Hint(ipv6hint));
+        }
+
+        Some(SvcbRecord {
+            priority: 1,
+            target,
+            params,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        rdata.extend_from_slice(&self.priority.to_be_bytes());
+        rdata.extend_from_slice(&self.target.serialize());
+
+        // SvcParams must be in strictly increasing key order
+        let mut params = self.params.iter().collect::<Vec<&SvcParam>>();
+        params.sort_by_key(|p| p.key());
+        for param in params {
+            let value = param.serialize_value();
+            rdata.extend_from_slice(&param.key().to_be_bytes());
+            rdata.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            rdata.extend_from_slice(&value);
+        }
+        rdata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svcb_serialize_sorts_params() {
+        let record = SvcbRecord {
+            priority: 1,
+            target: DnsName { labels: vec![] },
+            params: vec![
+                SvcParam::Ipv4Hint(vec![Ipv4Addr::new(1, 2, 3, 4)]),
+                SvcParam::Alpn(vec!["h2".to_string(), "h3".to_string()]),
+            ],
+        };
+        assert_eq!(
+            record.serialize(),
+            vec![
+                0, 1, // priority
+                0, // target "."
+                0, 1, 0, 6, 2, b'h', b'2', 2, b'h', b'3', // alpn
+                0, 4, 0, 4, 1, 2, 3, 4, // ipv4hint
+            ]
+        );
+    }
+
+    #[test]
+    fn test_svcb_service_mode_falls_back_to_gateway_hints() {
+        let config = HttpsConfig {
+            ipv6hint: vec![Ipv6Addr::LOCALHOST],
+            ..Default::default()
+        };
+        let record = SvcbRecord::service_mode(&config, None, None, vec![Ipv4Addr::new(1, 2, 3, 4)], vec![]).unwrap();
+        assert_eq!(
+            record.params,
+            vec![
+                SvcParam::Ipv4Hint(vec![Ipv4Addr::new(1, 2, 3, 4)]),
+                SvcParam::Ipv6Hint(vec![Ipv6Addr::LOCALHOST]),
+            ]
+        );
+        assert_eq!(SvcbRecord::service_mode(&HttpsConfig::default(), None, None, vec![], vec![]), None);
+    }
+
+    #[test]
+    fn test_svcb_service_mode_ech_only_for_gateway() {
+        let config = HttpsConfig {
+            ech: Some(vec![1, 2, 3]),
+            ipv4hint: vec![Ipv4Addr::new(1, 2, 3, 4)],
+            ipv6hint: vec![Ipv6Addr::LOCALHOST],
+            ..Default::default()
+        };
+        let record = SvcbRecord::service_mode(&config, None, None, vec![], vec![]).unwrap();
+        assert!(record.params.contains(&SvcParam::Ech(vec![1, 2, 3])));
+
+        // the name's origin holds no key for the gateway's ECH config
+        let record = SvcbRecord::service_mode(&config, None, None, vec![Ipv4Addr::new(5, 6, 7, 8)], vec![]).unwrap();
+        assert_eq!(
+            record.params,
+            vec![
+                SvcParam::Ipv4Hint(vec![Ipv4Addr::new(5, 6, 7, 8)]),
+                SvcParam::Ipv6Hint(vec![Ipv6Addr::LOCALHOST]),
+            ]
+        );
+        let target = DnsName::from("host.example".to_string());
+        let record = SvcbRecord::service_mode(&config, Some(target), None, vec![], vec![]).unwrap();
+        assert!(record.params.is_empty());
+    }
+
+    #[test]
+    fn test_svcb_service_mode_hostname_target() {
+        let config = HttpsConfig {
+            port: Some(8443),
+            ipv4hint: vec![Ipv4Addr::new(1, 2, 3, 4)],
+            ..Default::default()
+        };
+        let target = DnsName::from("host.example".to_string());
+        let record = SvcbRecord::service_mode(&config, Some(target.clone()), Some(444), vec![], vec![]).unwrap();
+        assert_eq!(record.target, target);
+        assert_eq!(record.params, vec![SvcParam::Port(444)]);
+    }
+}
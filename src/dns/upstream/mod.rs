@@ -0,0 +1,105 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use nom::{
+    multi::length_data,
+    number::complete::{be_u16, be_u32},
+    sequence::tuple,
+};
+use tokio::net::UdpSocket;
+
+use super::proto::{DnsFlags, DnsHeader, DnsName, DnsQuestion, Opcode, Parseable, RCode};
+use super::DnsError;
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Resolves `name` through a recursive upstream resolver and returns the rdata of every
+/// answer of type `qtype`, CNAMEs along the way are followed by the upstream.
+pub async fn resolve(upstream: SocketAddr, name: &DnsName, qtype: u16) -> Result<Vec<Vec<u8>>, DnsError> {
+    let id: u16 = rand::random();
+    let header = DnsHeader {
+        id,
+        flags: DnsFlags { qr: false, opcode: Opcode::Query, aa: false, tc: false, rd: true, ra: false, rcode: RCode::NoError },
+        qd_count: 1,
+        an_count: 0,
+        ns_count: 0,
+        ar_count: 0,
+    };
+    let question = DnsQuestion { qname: name.clone(), qtype, qclass: 1 };
+    let mut query = header.serialize();
+    query.extend_from_slice(&question.serialize());
+
+    let bind: SocketAddr = if upstream.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }.parse().unwrap();
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(upstream).await?;
+    socket.send(&query).await?;
+
+    let mut buf = [0u8; 4096];
+    let size = tokio::time::timeout(UPSTREAM_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+    parse_answers(&buf[0..size], id, qtype).ok_or(DnsError::InvalidUpstreamResponse)
+}
+
+fn parse_answers(message: &[u8], id: u16, qtype: u16) -> Option<Vec<Vec<u8>>> {
+    let (mut input, header) = DnsHeader::parse(message).ok()?;
+    if header.id != id || !header.flags.qr {
+        return None;
+    }
+    for _ in 0..header.qd_count {
+        input = skip_name(input)?.get(4..)?;
+    }
+
+    let mut rdatas = Vec::new();
+    for _ in 0..header.an_count {
+        input = skip_name(input)?;
+        let (rest, (rtype, _rclass, _ttl, rdata)) =
+            tuple((be_u16::<_, nom::error::Error<&[u8]>>, be_u16, be_u32, length_data(be_u16)))(input).ok()?;
+        if rtype == qtype {
+            rdatas.push(rdata.to_vec());
+        }
+        input = rest;
+    }
+    Some(rdatas)
+}
+
+/// Skips a possibly compressed name, a compression pointer always ends the name.
+fn skip_name(mut input: &[u8]) -> Option<&[u8]> {
+    loop {
+        let len = *input.first()?;
+        if len & 0b1100_0000 == 0b1100_0000 {
+            return input.get(2..);
+        }
+        input = input.get(1 + len as usize..)?;
+        if len == 0 {
+            return Some(input);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_follows_compressed_answers() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let upstream = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (size, src) = server.recv_from(&mut buf).await.unwrap();
+            let query = &buf[0..size];
+            let mut response = query[0..2].to_vec(); // id
+            response.extend_from_slice(&[0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0]);
+            response.extend_from_slice(&query[12..]); // question
+            // CNAME host.example -> edge.example, compressed against the question name
+            response.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 7, 4, b'e', b'd', b'g', b'e', 0xc0, 17]);
+            response.extend_from_slice(&[0xc0, 42, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4]);
+            server.send_to(&response, src).await.unwrap();
+        });
+
+        let rdatas = resolve(upstream, &DnsName::from("host.example".to_string()), 1).await.unwrap();
+        assert_eq!(rdatas, vec![vec![1, 2, 3, 4]]);
+    }
+}
@@ -0,0 +1,84 @@
+use super::proto::{DnsName, Parseable};
+
+/// The zone this server is authoritative for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneConfig {
+    pub apex: DnsName,
+    pub nameservers: Vec<DnsName>,
+    pub hostmaster: DnsName,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub minimum: u32,
+}
+
+impl ZoneConfig {
+    pub fn new(apex: DnsName, nameservers: Vec<DnsName>, hostmaster: DnsName) -> Self {
+        ZoneConfig {
+            apex,
+            nameservers,
+            hostmaster,
+            refresh: 3600,
+            retry: 600,
+            expire: 604800,
+            minimum: 300,
+        }
+    }
+
+    pub fn contains(&self, name: &DnsName) -> bool {
+        name.is_subdomain_of(&self.apex)
+    }
+
+    pub fn is_apex(&self, name: &DnsName) -> bool {
+        name.labels.len() == self.apex.labels.len() && self.contains(name)
+    }
+
+    /// The primary nameserver is the first configured one, or the apex itself.
+    fn mname(&self) -> &DnsName {
+        self.nameservers.first().unwrap_or(&self.apex)
+    }
+
+    pub fn soa_rdata(&self, serial: u32) -> Vec<u8> {
+        let mut rdata = self.mname().serialize();
+        rdata.extend_from_slice(&self.hostmaster.serialize());
+        rdata.extend_from_slice(&serial.to_be_bytes());
+        rdata.extend_from_slice(&self.refresh.to_be_bytes());
+        rdata.extend_from_slice(&self.retry.to_be_bytes());
+        rdata.extend_from_slice(&self.expire.to_be_bytes());
+        rdata.extend_from_slice(&self.minimum.to_be_bytes());
+        rdata
+    }
+
+    pub fn ns_rdatas(&self) -> Vec<Vec<u8>> {
+        self.nameservers.iter().map(|x| x.serialize()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_contains_is_case_insensitive() {
+        let zone = ZoneConfig::new(DnsName::from("eth".to_string()), vec![], DnsName::from("hostmaster.eth".to_string()));
+        assert!(zone.contains(&DnsName::from("Vitalik.ETH".to_string())));
+        assert!(zone.is_apex(&DnsName::from("eth".to_string())));
+        assert!(!zone.is_apex(&DnsName::from("vitalik.eth".to_string())));
+        assert!(!zone.contains(&DnsName::from("example.com".to_string())));
+    }
+
+    #[test]
+    fn test_zone_soa_rdata() {
+        let zone = ZoneConfig::new(
+            DnsName::from("eth".to_string()),
+            vec![DnsName::from("ns1.eth".to_string())],
+            DnsName::from("hostmaster.eth".to_string()),
+        );
+        let rdata = zone.soa_rdata(18_000_000);
+        let mut expected = vec![3, b'n', b's', b'1', 3, b'e', b't', b'h', 0];
+        expected.extend_from_slice(&[10, b'h', b'o', b's', b't', b'm', b'a', b's', b't', b'e', b'r', 3, b'e', b't', b'h', 0]);
+        expected.extend_from_slice(&18_000_000u32.to_be_bytes());
+        expected.extend_from_slice(&[0, 0, 14, 16, 0, 0, 2, 88, 0, 9, 58, 128, 0, 0, 1, 44]);
+        assert_eq!(rdata, expected);
+    }
+}
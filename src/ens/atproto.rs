@@ -0,0 +1,58 @@
+use crate::dns::{DnsError, DnsName};
+
+/// Text key the handle's DID is stored under, it is also the DNS label atproto clients query.
+pub const TEXT_KEY: &str = "_atproto";
+
+/// Parses `_atproto.<name>` into the ENS name.
+pub fn parse_query(qname: &DnsName) -> Option<DnsName> {
+    let [first, rest @ ..] = qname.labels.as_slice() else {
+        return None;
+    };
+    (first.label.eq_ignore_ascii_case(TEXT_KEY.as_bytes()) && !rest.is_empty()).then(|| DnsName { labels: rest.to_vec() })
+}
+
+/// atproto only supports `did:plc` and `did:web` identifiers.
+fn is_valid_did(did: &str) -> bool {
+    if let Some(id) = did.strip_prefix("did:plc:") {
+        return id.len() == 24 && id.chars().all(|x| x.is_ascii_lowercase() || ('2'..='7').contains(&x));
+    }
+    if let Some(host) = did.strip_prefix("did:web:") {
+        let labels: Vec<&str> = host.split('.').collect();
+        return labels.len() >= 2
+            && labels.iter().all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|x| x.is_ascii_lowercase() || x.is_ascii_digit() || x == '-')
+            });
+    }
+    false
+}
+
+/// Renders the stored DID, with or without a `did=` prefix, as the `did=<did>` TXT value
+/// atproto handle resolution expects.
+pub fn format_did(value: &str) -> Result<String, DnsError> {
+    let value = value.trim();
+    let did = value.strip_prefix("did=").unwrap_or(value);
+    if !is_valid_did(did) {
+        return Err(DnsError::InvalidAtprotoDid(value.to_string()));
+    }
+    Ok(format!("did={}", did))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_did() {
+        assert_eq!(format_did("did:plc:z72i7hdynmk6r22z27h6tvur").unwrap(), "did=did:plc:z72i7hdynmk6r22z27h6tvur");
+        assert_eq!(format_did(" did=did:web:example.com ").unwrap(), "did=did:web:example.com");
+        assert!(format_did("did:plc:Z72i7hdynmk6r22z27h6tvur").is_err());
+        assert!(format_did("did:plc:z72i7hdynmk6r22z27h6tv").is_err());
+        assert!(format_did("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK").is_err());
+        assert!(format_did("did:web:localhost").is_err());
+        assert!(format_did("https://bsky.app/profile/vitalik.eth").is_err());
+    }
+}
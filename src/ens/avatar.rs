@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ethers::types::{Address, U256};
+use url::Url;
+
+use super::fetch;
+use crate::dns::{DnsError, DnsName};
+
+const METADATA_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Gateways used to fetch NFT metadata and to render the final avatar URI over https.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvatarConfig {
+    pub ipfs_gateway: String,
+    pub arweave_gateway: String,
+}
+
+impl Default for AvatarConfig {
+    fn default() -> Self {
+        AvatarConfig {
+            ipfs_gateway: "https://ipfs.io".to_string(),
+            arweave_gateway: "https://arweave.net".to_string(),
+        }
+    }
+}
+
+impl AvatarConfig {
+    /// Rewrites `ipfs://`, `ipns://` and `ar://` URIs to their gateway URL, other URIs are kept as-is.
+    pub fn gateway_uri(&self, uri: &str) -> String {
+        let gateway = |base: &str, path: &str| format!("{}/{}", base.trim_end_matches('/'), path);
+        if let Some(path) = uri.strip_prefix("ipfs://") {
+            gateway(&self.ipfs_gateway, &format!("ipfs/{}", path.trim_start_matches("ipfs/")))
+        } else if let Some(path) = uri.strip_prefix("ipns://") {
+            gateway(&self.ipfs_gateway, &format!("ipns/{}", path))
+        } else if let Some(path) = uri.strip_prefix("ar://") {
+            gateway(&self.arweave_gateway, path)
+        } else {
+            uri.to_string()
+        }
+    }
+
+    /// Hosts of the gateways, which may be fetched whatever they resolve to.
+    fn gateway_hosts(&self) -> Vec<String> {
+        [&self.ipfs_gateway, &self.arweave_gateway]
+            .iter()
+            .filter_map(|x| Url::parse(x).ok()?.host_str().map(str::to_string))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NftStandard {
+    Erc721,
+    Erc1155,
+}
+
+/// An ENSIP-12 avatar text record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AvatarReference {
+    Uri(String),
+    Nft { chain_id: u64, standard: NftStandard, contract: Address, token_id: U256 },
+}
+
+impl AvatarReference {
+    /// Parses `eip155:<chain>/erc721:<contract>/<token>` and `.../erc1155:...` NFT references,
+    /// anything else is taken to be a URI.
+    pub fn parse(avatar: &str) -> Option<AvatarReference> {
+        let avatar = avatar.trim();
+        if avatar.is_empty() {
+            return None;
+        }
+        if !avatar.to_ascii_lowercase().starts_with("eip155:") {
+            return Some(AvatarReference::Uri(avatar.to_string()));
+        }
+        let mut parts = avatar["eip155:".len()..].split('/');
+        let chain_id = parts.next()?.parse().ok()?;
+        let (standard, contract) = parts.next()?.split_once(':')?;
+        let standard = match standard.to_ascii_lowercase().as_str() {
+            "erc721" => NftStandard::Erc721,
+            "erc1155" => NftStandard::Erc1155,
+            _ => return None,
+        };
+        let token_id = U256::from_dec_str(parts.next()?).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(AvatarReference::Nft { chain_id, standard, contract: contract.parse().ok()?, token_id })
+    }
+}
+
+/// Parses `_avatar-uri.<name>` into the ENS name.
+pub fn parse_query(qname: &DnsName) -> Option<DnsName> {
+    let [first, rest @ ..] = qname.labels.as_slice() else {
+        return None;
+    };
+    (first.label.eq_ignore_ascii_case(b"_avatar-uri") && !rest.is_empty()).then(|| DnsName { labels: rest.to_vec() })
+}
+
+/// ERC-1155 metadata URIs substitute `{id}` with the zero padded lowercase hex token id.
+pub fn expand_token_uri(uri: &str, standard: NftStandard, token_id: U256) -> String {
+    match standard {
+        NftStandard::Erc721 => uri.to_string(),
+        NftStandard::Erc1155 => uri.replace("{id}", &format!("{:064x}", token_id)),
+    }
+}
+
+/// The image of ERC-721/1155 metadata, inline `image_data` SVGs are returned as a data URI.
+pub fn metadata_image(metadata: &serde_json::Value) -> Option<String> {
+    ["image", "image_url"]
+        .iter()
+        .find_map(|key| metadata.get(key)?.as_str().filter(|x| !x.is_empty()).map(str::to_string))
+        .or_else(|| {
+            let svg = metadata.get("image_data")?.as_str()?;
+            Some(format!("data:image/svg+xml;base64,{}", STANDARD.encode(svg)))
+        })
+}
+
+/// Fetches token metadata, `data:application/json` URIs are decoded in place. Other URIs must be
+/// https or on one of the gateways.
+pub async fn fetch_metadata(config: &AvatarConfig, uri: &str) -> Result<serde_json::Value, DnsError> {
+    let error = |e: &dyn std::fmt::Display| DnsError::AvatarMetadataError(e.to_string());
+    if let Some(data) = uri.strip_prefix("data:application/json;base64,") {
+        let json = STANDARD.decode(data).map_err(|e| error(&e))?;
+        return serde_json::from_slice(&json).map_err(|e| error(&e));
+    }
+    if let Some((_, json)) = uri.strip_prefix("data:application/json").and_then(|x| x.split_once(',')) {
+        return serde_json::from_str(json).map_err(|e| error(&e));
+    }
+
+    let url = Url::parse(&config.gateway_uri(uri)).map_err(|e| error(&e))?;
+    let client = fetch::client_for(&url, &config.gateway_hosts(), METADATA_TIMEOUT).await.map_err(|e| error(&e))?;
+    let response = client.get(url).send().await.and_then(|x| x.error_for_status()).map_err(|e| error(&e))?;
+    let body = fetch::read_body(response).await.map_err(|e| error(&e))?;
+    serde_json::from_slice(&body).map_err(|e| error(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reference() {
+        assert_eq!(
+            AvatarReference::parse("eip155:1/erc721:0xb7F7F6C52F2e2fdb1963Eab30438024864c313F6/2430"),
+            Some(AvatarReference::Nft {
+                chain_id: 1,
+                standard: NftStandard::Erc721,
+                contract: "0xb7F7F6C52F2e2fdb1963Eab30438024864c313F6".parse().unwrap(),
+                token_id: 2430.into(),
+            })
+        );
+        assert_eq!(
+            AvatarReference::parse("https://example.com/a.png"),
+            Some(AvatarReference::Uri("https://example.com/a.png".to_string()))
+        );
+        assert_eq!(AvatarReference::parse("eip155:1/erc20:0xb7F7F6C52F2e2fdb1963Eab30438024864c313F6/1"), None);
+        assert_eq!(AvatarReference::parse(""), None);
+    }
+
+    #[test]
+    fn test_uris() {
+        let config = AvatarConfig::default();
+        assert_eq!(config.gateway_uri("ipfs://ipfs/QmHash/1.json"), "https://ipfs.io/ipfs/QmHash/1.json");
+        assert_eq!(config.gateway_uri("ar://txid"), "https://arweave.net/txid");
+        assert_eq!(config.gateway_uri("https://example.com"), "https://example.com");
+        assert_eq!(
+            expand_token_uri("https://x/{id}.json", NftStandard::Erc1155, 0x4d2.into()),
+            format!("https://x/{}4d2.json", "0".repeat(61))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_refuses_private_hosts() {
+        let config = AvatarConfig::default();
+        for uri in ["http://example.com/1.json", "https://127.0.0.1/1.json", "https://169.254.169.254/latest/meta-data"] {
+            assert!(matches!(fetch_metadata(&config, uri).await, Err(DnsError::AvatarMetadataError(_))), "{}", uri);
+        }
+        let config = AvatarConfig { ipfs_gateway: "http://127.0.0.1:1".to_string(), ..Default::default() };
+        assert_eq!(config.gateway_hosts(), vec!["127.0.0.1".to_string(), "arweave.net".to_string()]);
+    }
+
+    #[test]
+    fn test_metadata_image() {
+        assert_eq!(metadata_image(&serde_json::json!({"image": "ipfs://QmHash"})).unwrap(), "ipfs://QmHash");
+        assert_eq!(metadata_image(&serde_json::json!({"image_data": "<svg/>"})).unwrap(), "data:image/svg+xml;base64,PHN2Zy8+");
+        assert_eq!(metadata_image(&serde_json::json!({"name": "x"})), None);
+    }
+}
@@ -0,0 +1,69 @@
+use std::future::Future;
+
+use ethers::types::{BlockId, BlockNumber, H256, U64};
+
+tokio::task_local! {
+    static PINNED_BLOCK: PinnedBlock;
+}
+
+/// The block every call made while answering one question runs against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinnedBlock {
+    pub number: U64,
+    pub hash: H256,
+    /// State root storage proofs are verified against.
+    pub state_root: H256,
+}
+
+impl PinnedBlock {
+    /// EIP-1898 block hash, calls fail rather than read another fork after a reorg.
+    pub fn id(&self) -> BlockId {
+        BlockId::Hash(self.hash)
+    }
+
+    /// Runs `f` with calls pinned to this block.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        PINNED_BLOCK.scope(self, f).await
+    }
+}
+
+/// Which block questions are pinned to: `confirmations` blocks below the block at `tag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockConfig {
+    pub tag: BlockNumber,
+    pub confirmations: u64,
+}
+
+impl Default for BlockConfig {
+    fn default() -> Self {
+        BlockConfig { tag: BlockNumber::Latest, confirmations: 0 }
+    }
+}
+
+impl std::fmt::Display for BlockConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.confirmations {
+            0 => write!(f, "{}", self.tag),
+            confirmations => write!(f, "{} - {} confirmations", self.tag, confirmations),
+        }
+    }
+}
+
+/// The block of the question being answered, `None` outside of `PinnedBlock::scope`.
+pub fn current() -> Option<BlockId> {
+    pinned().map(|x| x.id())
+}
+
+pub fn pinned() -> Option<PinnedBlock> {
+    PINNED_BLOCK.try_with(|x| *x).ok()
+}
+
+/// Block tags questions can be pinned to.
+pub fn parse_tag(value: &str) -> Option<BlockNumber> {
+    match value.to_ascii_lowercase().as_str() {
+        "latest" => Some(BlockNumber::Latest),
+        "safe" => Some(BlockNumber::Safe),
+        "finalized" => Some(BlockNumber::Finalized),
+        _ => None,
+    }
+}
@@ -0,0 +1,401 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ethers::abi::{self, ParamType, Token};
+use ethers::types::{Address, Log, H256, U256, U64};
+use ethers::utils::keccak256;
+
+use super::block::PinnedBlock;
+use crate::dns::{DnsQuestion, Parseable};
+
+/// Most answers kept, the cache starts over when it is full.
+const MAX_ENTRIES: usize = 100_000;
+/// Age after which an answer is resolved again, bounding what a missed log can keep stale.
+const MAX_ENTRY_AGE: Duration = Duration::from_secs(600);
+/// Blocks the logs may be applied behind the newest block seen before answers are not served.
+const MAX_SYNC_LAG: u64 = 10;
+/// Time since logs were last applied after which answers are not served, e.g. while the RPC
+/// endpoint is down.
+const MAX_SYNC_AGE: Duration = Duration::from_secs(60);
+
+/// Registry events, only trusted from the registry itself.
+const REGISTRY_EVENTS: &[&str] = &["NewResolver(bytes32,address)", "Transfer(bytes32,address)"];
+
+/// PublicResolver events, the record changed is decoded from the log data.
+const RESOLVER_EVENTS: &[&str] = &[
+    "TextChanged(bytes32,string,string)",
+    "TextChanged(bytes32,string,string,string)",
+    "AddrChanged(bytes32,address)",
+    "AddressChanged(bytes32,uint256,bytes)",
+    "ContenthashChanged(bytes32,bytes)",
+    "DNSRecordChanged(bytes32,bytes,uint16,bytes)",
+    "DNSRecordDeleted(bytes32,bytes,uint16)",
+    "VersionChanged(bytes32,uint64)",
+];
+
+/// A record of a name an answer was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    /// The resolver the registry has for the name.
+    Resolver,
+    Text(String),
+    Addr(U256),
+    Contenthash,
+    /// EIP-1185 records of the DNS name with this hash and type.
+    Dns(H256, u16),
+}
+
+/// What a log says has changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The resolver or owner of the name, anything read about it.
+    Name(H256),
+    /// Every record of the name, cleared by a new record version.
+    Records(H256),
+    Record(H256, Record),
+}
+
+impl Change {
+    fn node(&self) -> H256 {
+        match self {
+            Change::Name(x) | Change::Records(x) | Change::Record(x, _) => *x,
+        }
+    }
+
+    fn affects(&self, node: H256, record: &Record) -> bool {
+        match self {
+            Change::Name(x) => *x == node,
+            Change::Records(x) => *x == node && *record != Record::Resolver,
+            Change::Record(x, changed) => *x == node && changed == record,
+        }
+    }
+}
+
+/// The records one resolution read. Resolutions that depended on anything whose changes are
+/// not logged, offchain gateways, wildcard resolvers or NFT metadata, are not cacheable.
+#[derive(Debug, Default)]
+pub struct Reads {
+    records: Vec<(H256, Record)>,
+    cacheable: bool,
+}
+
+tokio::task_local! {
+    static READS: RefCell<Reads>;
+}
+
+/// Runs `f` and returns what it read along with its output.
+pub async fn track<F: Future>(f: F) -> (F::Output, Reads) {
+    let reads = RefCell::new(Reads { records: vec![], cacheable: true });
+    READS
+        .scope(reads, async {
+            let output = f.await;
+            (output, READS.with(|x| x.take()))
+        })
+        .await
+}
+
+/// Notes that the current resolution read `record` of `node`.
+pub fn read(node: H256, record: Record) {
+    let _ = READS.try_with(|x| x.borrow_mut().records.push((node, record)));
+}
+
+/// Notes that the current resolution must not be cached.
+pub fn uncacheable() {
+    let _ = READS.try_with(|x| x.borrow_mut().cacheable = false);
+}
+
+/// The record a resolver call reads, `None` for records whose changes are not followed.
+pub fn record_of_call(signature: &str, params: &[Token]) -> Option<Record> {
+    match (signature, params) {
+        ("text(bytes32,string)", [Token::String(key)]) => Some(Record::Text(key.clone())),
+        ("addr(bytes32)", []) => Some(Record::Addr(60.into())),
+        ("addr(bytes32,uint256)", [Token::Uint(coin_type)]) => Some(Record::Addr(*coin_type)),
+        ("contenthash(bytes32)", []) => Some(Record::Contenthash),
+        ("dnsRecord(bytes32,bytes32,uint16)", [Token::FixedBytes(name), Token::Uint(resource)]) if name.len() == 32 => {
+            Some(Record::Dns(H256::from_slice(name), resource.low_u32() as u16))
+        }
+        _ => None,
+    }
+}
+
+fn topic(signature: &str) -> H256 {
+    keccak256(signature).into()
+}
+
+/// Topics of every followed event, for an `eth_getLogs` filter.
+pub fn topics() -> Vec<H256> {
+    REGISTRY_EVENTS.iter().chain(RESOLVER_EVENTS).map(|x| topic(x)).collect()
+}
+
+/// What `log` changed, `None` for logs of other events and registry events not emitted by
+/// `registry`.
+pub fn change_of(log: &Log, registry: Address) -> Option<Change> {
+    let signature = REGISTRY_EVENTS.iter().chain(RESOLVER_EVENTS).find(|x| Some(&topic(x)) == log.topics.first())?;
+    let node = *log.topics.get(1)?;
+    let data = |params: &[ParamType]| abi::decode(params, &log.data).ok();
+    let record = match *signature {
+        "NewResolver(bytes32,address)" | "Transfer(bytes32,address)" => {
+            return (log.address == registry).then_some(Change::Name(node));
+        }
+        "VersionChanged(bytes32,uint64)" => return Some(Change::Records(node)),
+        "TextChanged(bytes32,string,string)" => Record::Text(data(&[ParamType::String])?.remove(0).into_string()?),
+        "TextChanged(bytes32,string,string,string)" => {
+            Record::Text(data(&[ParamType::String, ParamType::String])?.remove(0).into_string()?)
+        }
+        "AddrChanged(bytes32,address)" => Record::Addr(60.into()),
+        "AddressChanged(bytes32,uint256,bytes)" => Record::Addr(data(&[ParamType::Uint(256), ParamType::Bytes])?.remove(0).into_uint()?),
+        "ContenthashChanged(bytes32,bytes)" => Record::Contenthash,
+        // (bytes name, uint16 resource[, bytes record])
+        _ => {
+            let mut tokens = data(&[ParamType::Bytes, ParamType::Uint(16)])?.into_iter();
+            let name = tokens.next()?.into_bytes()?;
+            let resource = tokens.next()?.into_uint()?;
+            Record::Dns(keccak256(name).into(), resource.low_u32() as u16)
+        }
+    };
+    Some(Change::Record(node, record))
+}
+
+type Key = (Vec<u8>, u16);
+
+fn key(question: &DnsQuestion) -> Key {
+    (question.qname.serialize(), question.qtype)
+}
+
+#[derive(Debug)]
+struct Entry {
+    answer: Option<String>,
+    records: Vec<(H256, Record)>,
+    /// Number and hash of the block the answer was resolved at.
+    block: (U64, H256),
+    inserted: Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<Key, Entry>,
+    /// Keys of the entries that read a node, possibly of entries since dropped.
+    by_node: HashMap<H256, HashSet<Key>>,
+    /// The block up to which logs are applied.
+    synced: Option<PinnedBlock>,
+    synced_at: Option<Instant>,
+    /// The newest block seen.
+    head: Option<U64>,
+}
+
+impl State {
+    /// Whether the logs are applied recently and close enough to the head to serve answers.
+    fn is_current(&self) -> bool {
+        let (Some(synced), Some(synced_at)) = (self.synced, self.synced_at) else {
+            return false;
+        };
+        let lag = self.head.unwrap_or_default().saturating_sub(synced.number);
+        synced_at.elapsed() <= MAX_SYNC_AGE && lag <= MAX_SYNC_LAG.into()
+    }
+}
+
+/// Answers kept until a log changes a record they were read from.
+#[derive(Debug, Default)]
+pub struct AnswerCache {
+    state: Mutex<State>,
+}
+
+impl AnswerCache {
+    /// The cached answer, `Some(None)` for a cached lack of one. Nothing is served while the logs
+    /// are not applied up to the head.
+    pub fn get(&self, question: &DnsQuestion) -> Option<Option<String>> {
+        let state = self.state.lock().unwrap();
+        if !state.is_current() {
+            return None;
+        }
+        state.entries.get(&key(question)).filter(|x| x.inserted.elapsed() <= MAX_ENTRY_AGE).map(|x| x.answer.clone())
+    }
+
+    /// Notes a block seen at the head of the chain.
+    pub fn observe(&self, block: U64) {
+        let mut state = self.state.lock().unwrap();
+        state.head = state.head.max(Some(block));
+    }
+
+    /// Caches the answer resolved at `block`. Answers from before the block logs are applied up
+    /// to could have missed a change and are not cached.
+    pub fn insert(&self, question: &DnsQuestion, block: &PinnedBlock, answer: &Option<String>, reads: Reads) {
+        let mut state = self.state.lock().unwrap();
+        state.head = state.head.max(Some(block.number));
+        let Some(synced) = state.synced else {
+            return;
+        };
+        if !reads.cacheable || block.number < synced.number || (block.number == synced.number && block.hash != synced.hash) {
+            return;
+        }
+        if state.entries.len() >= MAX_ENTRIES {
+            state.entries.clear();
+            state.by_node.clear();
+        }
+        let key = key(question);
+        for (node, _) in &reads.records {
+            state.by_node.entry(*node).or_default().insert(key.clone());
+        }
+        let entry = Entry { answer: answer.clone(), records: reads.records, block: (block.number, block.hash), inserted: Instant::now() };
+        state.entries.insert(key, entry);
+    }
+
+    /// Blocks after the synced one up to `block` that answers were resolved at. Logs applied up
+    /// to `block` only cover these answers when the blocks are still canonical.
+    pub fn unconfirmed_blocks(&self, block: U64) -> Vec<(U64, H256)> {
+        let state = self.state.lock().unwrap();
+        let synced = state.synced.map(|x| x.number).unwrap_or_default();
+        let mut blocks: Vec<_> = state.entries.values().map(|x| x.block).filter(|(x, _)| *x > synced && *x <= block).collect();
+        blocks.sort();
+        blocks.dedup();
+        blocks
+    }
+
+    pub fn synced(&self) -> Option<PinnedBlock> {
+        self.state.lock().unwrap().synced
+    }
+
+    /// Drops the answers `changes` affect, which are all changes up to `block`. Answers resolved
+    /// after the synced block up to `block` are dropped too unless their block is `block` or one
+    /// of the `canonical` blocks, they may have been resolved at a block since reorged out.
+    pub fn apply(&self, changes: impl IntoIterator<Item = Change>, block: PinnedBlock, canonical: &[(U64, H256)]) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let synced = state.synced.map(|x| x.number).unwrap_or_default();
+        state.entries.retain(|_, entry| {
+            let (number, _) = entry.block;
+            number <= synced || number > block.number || entry.block == (block.number, block.hash) || canonical.contains(&entry.block)
+        });
+        for change in changes {
+            let Some(keys) = state.by_node.get_mut(&change.node()) else {
+                continue;
+            };
+            keys.retain(|key| {
+                let Some(entry) = state.entries.get(key) else {
+                    return false;
+                };
+                if entry.records.iter().any(|(node, record)| change.affects(*node, record)) {
+                    state.entries.remove(key);
+                    return false;
+                }
+                true
+            });
+        }
+        state.synced = Some(block);
+        state.synced_at = Some(Instant::now());
+    }
+
+    /// Drops every answer, e.g. after a reorg, and applies logs from `block` on.
+    pub fn reset(&self, block: PinnedBlock) {
+        let mut state = self.state.lock().unwrap();
+        *state = State { synced: Some(block), synced_at: Some(Instant::now()), head: state.head, ..Default::default() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::DnsName;
+    use ethers::types::U64;
+
+    fn question(name: &str) -> DnsQuestion {
+        DnsQuestion { qname: DnsName::from(name.to_string()), qtype: 16, qclass: 1 }
+    }
+
+    fn block(number: u64) -> PinnedBlock {
+        PinnedBlock { number: U64::from(number), hash: H256::from_low_u64_be(number), state_root: H256::zero() }
+    }
+
+    fn log(address: Address, signature: &str, node: H256, data: &[Token]) -> Log {
+        Log { address, topics: vec![topic(signature), node], data: abi::encode(data).into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_change_of() {
+        let registry = Address::repeat_byte(1);
+        let resolver = Address::repeat_byte(2);
+        let node = H256::repeat_byte(3);
+        let text = log(resolver, "TextChanged(bytes32,string,string,string)", node, &[Token::String("url".into()), Token::String("hi".into())]);
+        assert_eq!(change_of(&text, registry), Some(Change::Record(node, Record::Text("url".into()))));
+        let addr = log(resolver, "AddressChanged(bytes32,uint256,bytes)", node, &[Token::Uint(0.into()), Token::Bytes(vec![1])]);
+        assert_eq!(change_of(&addr, registry), Some(Change::Record(node, Record::Addr(0.into()))));
+        let dns = log(resolver, "DNSRecordChanged(bytes32,bytes,uint16,bytes)", node, &[Token::Bytes(vec![0]), Token::Uint(16.into()), Token::Bytes(vec![])]);
+        assert_eq!(change_of(&dns, registry), Some(Change::Record(node, Record::Dns(keccak256([0]).into(), 16))));
+
+        // registry events from anything but the registry are ignored
+        let new_resolver = log(registry, "NewResolver(bytes32,address)", node, &[Token::Address(resolver)]);
+        assert_eq!(change_of(&new_resolver, registry), Some(Change::Name(node)));
+        assert_eq!(change_of(&new_resolver, resolver), None);
+        assert_eq!(change_of(&log(resolver, "Transfer(address,address,uint256)", node, &[]), registry), None);
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let cache = AnswerCache::default();
+        let (vitalik, nick) = (H256::repeat_byte(1), H256::repeat_byte(2));
+        let reads = |records: Vec<(H256, Record)>| Reads { records, cacheable: true };
+        let url = || (vitalik, Record::Text("url".into()));
+        cache.insert(&question("vitalik.eth"), &block(10), &Some("a".into()), reads(vec![url()]));
+        assert_eq!(cache.get(&question("vitalik.eth")), None);
+
+        cache.reset(block(10));
+        cache.insert(&question("vitalik.eth"), &block(10), &Some("a".into()), reads(vec![(vitalik, Record::Resolver), url()]));
+        cache.insert(&question("_nostr.vitalik.eth"), &block(11), &None, reads(vec![(vitalik, Record::Resolver)]));
+        cache.insert(&question("nick.eth"), &block(10), &Some("c".into()), reads(vec![(nick, Record::Resolver), (nick, Record::Contenthash)]));
+        cache.insert(&question("avatar.eth"), &block(11), &Some("d".into()), Reads { records: vec![], cacheable: false });
+        assert_eq!(cache.get(&question("_nostr.vitalik.eth")), Some(None));
+        assert_eq!(cache.get(&question("avatar.eth")), None);
+
+        cache.apply([Change::Record(vitalik, Record::Text("url".into())), Change::Record(nick, Record::Text("url".into()))], block(11), &[]);
+        assert_eq!(cache.get(&question("vitalik.eth")), None);
+        assert_eq!(cache.get(&question("_nostr.vitalik.eth")), Some(None));
+        assert_eq!(cache.get(&question("nick.eth")), Some(Some("c".into())));
+        cache.apply([Change::Records(vitalik), Change::Name(nick)], block(12), &[]);
+        assert_eq!(cache.get(&question("_nostr.vitalik.eth")), Some(None));
+        assert_eq!(cache.get(&question("nick.eth")), None);
+
+        // answers from before the synced block may have missed a change
+        cache.insert(&question("nick.eth"), &block(11), &Some("c".into()), reads(vec![]));
+        assert_eq!(cache.get(&question("nick.eth")), None);
+
+        // answers resolved ahead of the synced block are dropped when their block was reorged out
+        let reorged = PinnedBlock { hash: H256::repeat_byte(0xee), ..block(13) };
+        cache.insert(&question("nick.eth"), &block(13), &Some("c".into()), reads(vec![]));
+        cache.insert(&question("vitalik.eth"), &block(14), &Some("a".into()), reads(vec![]));
+        cache.insert(&question("avatar.eth"), &reorged, &Some("d".into()), reads(vec![]));
+        assert_eq!(cache.unconfirmed_blocks(U64::from(14)), vec![(U64::from(13), block(13).hash), (U64::from(13), reorged.hash), (U64::from(14), block(14).hash)]);
+        cache.apply([], block(14), &[(U64::from(13), block(13).hash)]);
+        assert_eq!(cache.get(&question("nick.eth")), Some(Some("c".into())));
+        assert_eq!(cache.get(&question("vitalik.eth")), Some(Some("a".into())));
+        assert_eq!(cache.get(&question("avatar.eth")), None);
+    }
+
+    #[test]
+    fn test_stale_answers_not_served() {
+        let cache = AnswerCache::default();
+        let reads = || Reads { records: vec![], cacheable: true };
+        cache.reset(block(10));
+        cache.insert(&question("vitalik.eth"), &block(10), &Some("a".into()), reads());
+        assert_eq!(cache.get(&question("vitalik.eth")), Some(Some("a".into())));
+
+        // logs fall behind the head
+        cache.observe(U64::from(10 + MAX_SYNC_LAG + 1));
+        assert_eq!(cache.get(&question("vitalik.eth")), None);
+        cache.apply([], block(10 + MAX_SYNC_LAG + 1), &[]);
+        assert_eq!(cache.get(&question("vitalik.eth")), Some(Some("a".into())));
+
+        // logs were not applied for a while
+        let long_ago = Instant::now() - MAX_SYNC_AGE - Duration::from_secs(1);
+        cache.state.lock().unwrap().synced_at = Some(long_ago);
+        assert_eq!(cache.get(&question("vitalik.eth")), None);
+        cache.apply([], block(10 + MAX_SYNC_LAG + 1), &[]);
+
+        // answers expire
+        let long_ago = Instant::now() - MAX_ENTRY_AGE - Duration::from_secs(1);
+        cache.state.lock().unwrap().entries.values_mut().for_each(|x| x.inserted = long_ago);
+        assert_eq!(cache.get(&question("vitalik.eth")), None);
+    }
+}
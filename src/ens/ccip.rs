@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::{ProviderError, RpcError};
+use ethers::types::{Address, Bytes};
+use ethers::utils::hex;
+use url::Url;
+
+use super::fetch;
+use crate::dns::DnsError;
+
+/// `OffchainLookup(address,string[],bytes,bytes4,bytes)`
+const OFFCHAIN_LOOKUP_SELECTOR: [u8; 4] = [0x55, 0x6f, 0x18, 0x30];
+
+/// Limits on the EIP-3668 gateway calls made while resolving a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CcipConfig {
+    /// Gateway hosts that may be queried over http or https, a host also allows its subdomains.
+    /// Empty allows https gateways resolving to public addresses.
+    pub allowlist: Vec<String>,
+    /// Timeout of a single gateway request.
+    pub timeout: Duration,
+    /// Number of OffchainLookup reverts followed for one call, including those raised by callbacks.
+    pub max_redirects: usize,
+}
+
+impl Default for CcipConfig {
+    fn default() -> Self {
+        CcipConfig { allowlist: vec![], timeout: Duration::from_secs(10), max_redirects: 4 }
+    }
+}
+
+/// The arguments of an `OffchainLookup` revert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OffchainLookup {
+    pub sender: Address,
+    pub urls: Vec<String>,
+    pub call_data: Bytes,
+    pub callback_function: [u8; 4],
+    pub extra_data: Bytes,
+}
+
+impl OffchainLookup {
+    pub fn decode(revert: &[u8]) -> Option<OffchainLookup> {
+        let data = revert.strip_prefix(&OFFCHAIN_LOOKUP_SELECTOR)?;
+        let params = [
+            ParamType::Address,
+            ParamType::Array(Box::new(ParamType::String)),
+            ParamType::Bytes,
+            ParamType::FixedBytes(4),
+            ParamType::Bytes,
+        ];
+        let mut tokens = abi::decode(&params, data).ok()?.into_iter();
+        Some(OffchainLookup {
+            sender: tokens.next()?.into_address()?,
+            urls: tokens.next()?.into_array()?.into_iter().map(Token::into_string).collect::<Option<_>>()?,
+            call_data: tokens.next()?.into_bytes()?.into(),
+            callback_function: tokens.next()?.into_fixed_bytes()?.try_into().ok()?,
+            extra_data: tokens.next()?.into_bytes()?.into(),
+        })
+    }
+
+    /// Calldata of `callbackFunction(bytes response, bytes extraData)` on the sender.
+    pub fn callback_data(&self, response: &[u8]) -> Bytes {
+        let params = abi::encode(&[Token::Bytes(response.to_vec()), Token::Bytes(self.extra_data.to_vec())]);
+        [&self.callback_function[..], &params].concat().into()
+    }
+}
+
+/// The revert data of a failed `eth_call`, if the node returned any.
+pub fn revert_data(error: &ProviderError) -> Option<Bytes> {
+    error.as_error_response()?.as_revert_data()
+}
+
+impl CcipConfig {
+    /// A client for the gateway at `url`. With an empty allowlist any https gateway resolving to
+    /// public addresses is allowed, otherwise only allowlisted hosts.
+    async fn client_for(&self, url: &Url) -> Result<reqwest::Client, String> {
+        let host = url.host_str().unwrap_or_default();
+        if !self.allowlist.is_empty() && !self.allowlist.iter().any(|x| host == x || host.ends_with(&format!(".{}", x))) {
+            return Err(format!("gateway not allowed {}", url));
+        }
+        fetch::client_for(url, &self.allowlist, self.timeout).await
+    }
+
+    /// Queries the lookup's gateways in order. Server errors move on to the next gateway,
+    /// client errors end the lookup as required by EIP-3668.
+    pub async fn fetch(&self, lookup: &OffchainLookup) -> Result<Bytes, DnsError> {
+        let error = |e: &dyn std::fmt::Display| DnsError::CcipReadError(e.to_string());
+        let sender = format!("{:?}", lookup.sender);
+        let data = format!("0x{}", hex::encode(&lookup.call_data));
+
+        let mut last_error = error(&"no gateway urls");
+        for template in &lookup.urls {
+            let request = if template.contains("{data}") {
+                Url::parse(&template.replace("{sender}", &sender).replace("{data}", &data)).map(|x| (x, None))
+            } else {
+                let body = serde_json::json!({ "data": data, "sender": sender });
+                Url::parse(&template.replace("{sender}", &sender)).map(|x| (x, Some(body)))
+            };
+            let (url, body) = match request {
+                Ok(x) => x,
+                Err(e) => {
+                    last_error = error(&e);
+                    continue;
+                }
+            };
+            let client = match self.client_for(&url).await {
+                Ok(x) => x,
+                Err(e) => {
+                    last_error = error(&e);
+                    continue;
+                }
+            };
+
+            let response = match body {
+                Some(body) => client.post(url).json(&body).send().await,
+                None => client.get(url).send().await,
+            };
+            let response = match response {
+                Ok(x) => x,
+                Err(e) => {
+                    last_error = error(&e);
+                    continue;
+                }
+            };
+            let status = response.status();
+            if status.is_client_error() {
+                return Err(error(&format!("gateway {} returned {}", template, status)));
+            }
+            if !status.is_success() {
+                last_error = error(&format!("gateway {} returned {}", template, status));
+                continue;
+            }
+            let body = fetch::read_body(response).await.map_err(|e| error(&e))?;
+            let json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| error(&e))?;
+            let data = json.get("data").and_then(|x| x.as_str()).ok_or_else(|| error(&"gateway response without data"))?;
+            return data.parse::<Bytes>().map_err(|e| error(&e));
+        }
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_offchain_lookup() {
+        let sender: Address = "0xc1735677a60884abbcf72295e88d47764beda282".parse().unwrap();
+        let data = abi::encode(&[
+            Token::Address(sender),
+            Token::Array(vec![Token::String("https://gateway.example/{sender}/{data}.json".to_string())]),
+            Token::Bytes(vec![1, 2]),
+            Token::FixedBytes(vec![0xaa, 0xbb, 0xcc, 0xdd]),
+            Token::Bytes(vec![3]),
+        ]);
+        let lookup = OffchainLookup::decode(&[&OFFCHAIN_LOOKUP_SELECTOR[..], &data].concat()).unwrap();
+        assert_eq!(lookup.sender, sender);
+        assert_eq!(lookup.callback_function, [0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(
+            lookup.callback_data(&[9]).to_vec(),
+            [&[0xaa, 0xbb, 0xcc, 0xdd][..], &abi::encode(&[Token::Bytes(vec![9]), Token::Bytes(vec![3])])].concat()
+        );
+        assert_eq!(OffchainLookup::decode(&data), None);
+    }
+
+    #[tokio::test]
+    async fn test_allowlist() {
+        let config = CcipConfig { allowlist: vec!["ccip.example".to_string()], ..Default::default() };
+        assert!(config.client_for(&"https://ccip.example/x".parse().unwrap()).await.is_ok());
+        assert!(config.client_for(&"http://eu.ccip.example/x".parse().unwrap()).await.is_ok());
+        assert!(config.client_for(&"https://evilccip.example/x".parse().unwrap()).await.is_err());
+        assert!(config.client_for(&"ftp://ccip.example/x".parse().unwrap()).await.is_err());
+        assert!(config.client_for(&"https://1.1.1.1/x".parse().unwrap()).await.is_err());
+
+        let config = CcipConfig::default();
+        assert!(config.client_for(&"https://1.1.1.1/x".parse().unwrap()).await.is_ok());
+        assert!(config.client_for(&"http://127.0.0.1/x".parse().unwrap()).await.is_err());
+        assert!(config.client_for(&"https://127.0.0.1/x".parse().unwrap()).await.is_err());
+        assert!(config.client_for(&"https://169.254.169.254/x".parse().unwrap()).await.is_err());
+        assert!(config.client_for(&"http://1.1.1.1/x".parse().unwrap()).await.is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use std::str::FromStr;
+
+use ethers::prelude::ens::ENS_ADDRESS;
+use ethers::types::{Address, Chain};
+
+/// Chains with the ENS registry at its canonical address.
+const REGISTRY_CHAINS: [Chain; 3] = [Chain::Mainnet, Chain::Sepolia, Chain::Holesky];
+
+/// Parses a chain name (`mainnet`, `sepolia`, `holesky`, `anvil`, ...) or a numeric chain id.
+pub fn parse_chain(value: &str) -> Option<u64> {
+    value
+        .parse::<u64>()
+        .ok()
+        .or_else(|| Chain::from_str(&value.to_ascii_lowercase()).ok().map(|x| x as u64))
+}
+
+/// The ENS registry of a chain ENS is deployed on, local deployments have to be configured.
+pub fn registry_address(chain_id: u64) -> Option<Address> {
+    REGISTRY_CHAINS.iter().any(|x| *x as u64 == chain_id).then_some(ENS_ADDRESS)
+}
+
+/// Whether the registry falls back to the registry ENS launched with for names that have no record
+/// of their own, as mainnet's ENSRegistryWithFallback does for names never migrated from it.
+pub fn has_registry_fallback(chain_id: u64, registry: Address) -> bool {
+    chain_id == Chain::Mainnet as u64 && registry == ENS_ADDRESS
+}
+
+/// A readable name of the chain for logs.
+pub fn chain_name(chain_id: u64) -> String {
+    Chain::try_from(chain_id).map(|x| x.to_string()).unwrap_or_else(|_| chain_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chains() {
+        assert_eq!(parse_chain("mainnet"), Some(1));
+        assert_eq!(parse_chain("Holesky"), Some(17000));
+        assert_eq!(parse_chain("anvil"), Some(31337));
+        assert_eq!(parse_chain("1337"), Some(1337));
+        assert_eq!(parse_chain("nowhere"), None);
+        assert_eq!(registry_address(11155111), Some(ENS_ADDRESS));
+        assert_eq!(registry_address(31337), None);
+        assert_eq!(chain_name(17000), "holesky");
+        assert!(has_registry_fallback(1, ENS_ADDRESS));
+        assert!(!has_registry_fallback(11155111, ENS_ADDRESS));
+        assert!(!has_registry_fallback(1, Address::repeat_byte(1)));
+    }
+}
@@ -0,0 +1,126 @@
+use bech32::{u5, ToBase32, Variant};
+use ethers::types::Address;
+use ethers::utils::{hex, to_checksum};
+
+use crate::dns::DnsName;
+
+/// ENSIP-11 coin types of EVM chains have the MSB set, the remaining bits are the chain id.
+const ENSIP11_EVM_FLAG: u64 = 0x8000_0000;
+
+/// SLIP-44 / ENSIP-11 coin types that can be queried by symbol.
+static COIN_SYMBOLS: &[(&str, u64)] = &[
+    ("btc", 0),
+    ("ltc", 2),
+    ("doge", 3),
+    ("eth", 60),
+    ("etc", 61),
+    ("sol", 501),
+    ("matic", 966),
+    ("op", ENSIP11_EVM_FLAG | 10),
+    ("gno", ENSIP11_EVM_FLAG | 100),
+    ("base", ENSIP11_EVM_FLAG | 8453),
+    ("arb1", ENSIP11_EVM_FLAG | 42161),
+];
+
+/// A coin type is either one of the known symbols or its decimal number.
+fn coin_type_of(label: &[u8]) -> Option<u64> {
+    let label = std::str::from_utf8(label).ok()?.to_ascii_lowercase();
+    COIN_SYMBOLS
+        .iter()
+        .find(|(symbol, _)| *symbol == label)
+        .map(|(_, coin_type)| *coin_type)
+        .or_else(|| label.parse::<u64>().ok())
+}
+
+/// Parses `_<symbol>.<name>` and `_addr.<coin>.<name>` into the coin type and the ENS name.
+pub fn parse_coin_query(qname: &DnsName) -> Option<(u64, DnsName)> {
+    let [first, rest @ ..] = qname.labels.as_slice() else {
+        return None;
+    };
+    if first.label.eq_ignore_ascii_case(b"_addr") {
+        let [coin, name @ ..] = rest else {
+            return None;
+        };
+        let coin_type = coin_type_of(&coin.label)?;
+        return (!name.is_empty()).then(|| (coin_type, DnsName { labels: name.to_vec() }));
+    }
+
+    let symbol = first.label.strip_prefix(b"_")?;
+    let coin_type = COIN_SYMBOLS
+        .iter()
+        .find(|(x, _)| x.as_bytes().eq_ignore_ascii_case(symbol))
+        .map(|(_, coin_type)| *coin_type)?;
+    (!rest.is_empty()).then(|| (coin_type, DnsName { labels: rest.to_vec() }))
+}
+
+/// Renders the bytes returned by `addr(node, coinType)` in the chain's native address format,
+/// coin types without a known format are rendered as hex.
+pub fn format_address(coin_type: u64, data: &[u8]) -> Option<String> {
+    match coin_type {
+        0 => format_bitcoin_like(data, 0x00, 0x05, Some("bc")),
+        2 => format_bitcoin_like(data, 0x30, 0x32, Some("ltc")),
+        3 => format_bitcoin_like(data, 0x1e, 0x16, None),
+        501 => (data.len() == 32).then(|| bs58::encode(data).into_string()),
+        60 | 61 | 966 => format_evm(data),
+        x if x & ENSIP11_EVM_FLAG != 0 => format_evm(data),
+        _ => Some(format!("0x{}", hex::encode(data))),
+    }
+}
+
+fn format_evm(data: &[u8]) -> Option<String> {
+    (data.len() == 20).then(|| to_checksum(&Address::from_slice(data), None))
+}
+
+/// Encodes a P2PKH, P2SH or segwit scriptPubKey as an address.
+fn format_bitcoin_like(script: &[u8], p2pkh_version: u8, p2sh_version: u8, segwit_hrp: Option<&str>) -> Option<String> {
+    match script {
+        [0x76, 0xa9, 0x14, hash @ .., 0x88, 0xac] if hash.len() == 20 => {
+            Some(bs58::encode(hash).with_check_version(p2pkh_version).into_string())
+        }
+        [0xa9, 0x14, hash @ .., 0x87] if hash.len() == 20 => {
+            Some(bs58::encode(hash).with_check_version(p2sh_version).into_string())
+        }
+        [opcode, len, program @ ..] if program.len() == *len as usize && (*opcode == 0x00 || (0x51..=0x60).contains(opcode)) => {
+            let version = if *opcode == 0x00 { 0 } else { opcode - 0x50 };
+            let variant = if version == 0 { Variant::Bech32 } else { Variant::Bech32m };
+            let mut data = vec![u5::try_from_u8(version).ok()?];
+            data.extend(program.to_base32());
+            bech32::encode(segwit_hrp?, data, variant).ok()
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_coin_query() {
+        let name = DnsName::from("vitalik.eth".to_string());
+        assert_eq!(parse_coin_query(&DnsName::from("_btc.vitalik.eth".to_string())), Some((0, name.clone())));
+        assert_eq!(parse_coin_query(&DnsName::from("_addr.501.vitalik.eth".to_string())), Some((501, name.clone())));
+        assert_eq!(parse_coin_query(&DnsName::from("_addr.base.vitalik.eth".to_string())), Some((2147492101, name)));
+        assert_eq!(parse_coin_query(&DnsName::from("_atproto.vitalik.eth".to_string())), None);
+        assert_eq!(parse_coin_query(&DnsName::from("_addr.btc".to_string())), None);
+    }
+
+    #[test]
+    fn test_format_address_bitcoin() {
+        let p2pkh = hex::decode("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac").unwrap();
+        assert_eq!(format_address(0, &p2pkh).unwrap(), "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa");
+        let p2sh = hex::decode("a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1887").unwrap();
+        assert_eq!(format_address(0, &p2sh).unwrap(), "3Ai1JZ8pdJb2ksieUV8FsxSNVJCpoPi8W6");
+        let segwit = hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(format_address(0, &segwit).unwrap(), "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+    }
+
+    #[test]
+    fn test_format_address_evm() {
+        let address = hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+        assert_eq!(format_address(60, &address).unwrap(), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert_eq!(format_address(ENSIP11_EVM_FLAG | 10, &address).unwrap(), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert_eq!(format_address(60, &address[1..]), None);
+        assert_eq!(format_address(9999, &[0xab, 0xcd]).unwrap(), "0xabcd");
+    }
+}
@@ -0,0 +1,82 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ethers::utils::hex;
+
+/// Reads an unsigned LEB128 varint as used by multicodec.
+fn read_varint(data: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value: u64 = 0;
+    for (i, byte) in data.iter().enumerate().take(9) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &data[i + 1..]));
+        }
+    }
+    None
+}
+
+/// RFC 4648 base32, lowercase and without padding as used by multibase `b`.
+fn base32_lower(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut out = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+/// Renders a CID as CIDv1 in multibase base32, CIDv0 (a bare sha256 multihash) is upgraded to dag-pb v1.
+fn cid_to_base32(cid: &[u8]) -> String {
+    if cid.len() == 34 && cid[0] == 0x12 && cid[1] == 0x20 {
+        return format!("b{}", base32_lower(&[&[0x01, 0x70][..], cid].concat()));
+    }
+    format!("b{}", base32_lower(cid))
+}
+
+/// Decodes an ENSIP-7 contenthash into a URI, unknown protocols are rendered as hex.
+pub fn to_uri(contenthash: &[u8]) -> Option<String> {
+    let (codec, value) = read_varint(contenthash)?;
+    let uri = match codec {
+        0xe3 => format!("ipfs://{}", cid_to_base32(value)),
+        0xe5 => format!("ipns://{}", cid_to_base32(value)),
+        0xe4 => {
+            // swarm-manifest CIDv1 with a keccak-256 multihash, bzz addresses are the bare hash
+            let hash = value.get(value.len().checked_sub(32)?..)?;
+            format!("bzz://{}", hex::encode(hash))
+        }
+        0x01bc | 0x01bd => format!("onion://{}", std::str::from_utf8(value).ok()?),
+        0xb19910 => format!("ar://{}", URL_SAFE_NO_PAD.encode(value)),
+        _ => format!("0x{}", hex::encode(contenthash)),
+    };
+    Some(uri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base32_lower() {
+        assert_eq!(base32_lower(b"foobar"), "mzxw6ytboi");
+        assert_eq!(base32_lower(b"f"), "my");
+    }
+
+    #[test]
+    fn test_to_uri() {
+        let ipfs = hex::decode("e3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f").unwrap();
+        assert!(to_uri(&ipfs).unwrap().starts_with("ipfs://bafybei"));
+        let swarm = hex::decode("e40101fa011b20d1de9994b4d039f6548d191eb26786769f580809256b4685ef316805265ea162").unwrap();
+        assert_eq!(to_uri(&swarm).unwrap(), "bzz://d1de9994b4d039f6548d191eb26786769f580809256b4685ef316805265ea162");
+        assert_eq!(to_uri(&[0x01, 0xab]).unwrap(), "0x01ab");
+        assert_eq!(to_uri(&[]), None);
+    }
+}
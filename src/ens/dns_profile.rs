@@ -0,0 +1,63 @@
+use ethers::utils::keccak256;
+
+use crate::dns::{DnsLabel, DnsName, DnsRecord, Parseable};
+
+/// The `name` argument of `dnsRecord(bytes32,bytes32,uint16)`: keccak256 of the lowercase wire
+/// format owner name, so queries with randomized case (DNS 0x20) find the same records.
+pub fn name_hash(qname: &DnsName) -> [u8; 32] {
+    let labels = qname.labels.iter().map(|x| DnsLabel { label: x.label.to_ascii_lowercase() }).collect();
+    keccak256(DnsName { labels }.serialize())
+}
+
+/// Parses the concatenated wire format resource records returned by `dnsRecord`,
+/// keeping those of type `rtype` owned by `qname`.
+pub fn parse_rrset(data: &[u8], qname: &DnsName, rtype: u16) -> Option<Vec<DnsRecord>> {
+    let mut input = data;
+    let mut records = Vec::new();
+    while !input.is_empty() {
+        let (rest, record) = DnsRecord::parse(input).ok()?;
+        if record.rtype == rtype && record.name.is_subdomain_of(qname) && record.name.labels.len() == qname.labels.len() {
+            records.push(record);
+        }
+        input = rest;
+    }
+    Some(records)
+}
+
+/// Joins the character-strings of TXT rdata into the value they were split from.
+pub fn txt_value(rdata: &[u8]) -> Option<String> {
+    let mut input = rdata;
+    let mut value = Vec::new();
+    while let Some((len, rest)) = input.split_first() {
+        value.extend_from_slice(rest.get(..*len as usize)?);
+        input = &rest[*len as usize..];
+    }
+    String::from_utf8(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_hash_ignores_case() {
+        let name_hash_of = |x: &str| name_hash(&DnsName::from(x.to_string()));
+        assert_eq!(name_hash_of("_DMarc.ViTaLiK.eTh"), name_hash_of("_dmarc.vitalik.eth"));
+        assert_eq!(name_hash_of("_dmarc.vitalik.eth"), keccak256(b"\x06_dmarc\x07vitalik\x03eth\x00"));
+        assert_eq!(DnsName::from("_DMarc.ViTaLiK.eTh".to_string()).punycode_decode().unwrap(), "_dmarc.vitalik.eth");
+    }
+
+    #[test]
+    fn test_parse_txt_rrset() {
+        let qname = DnsName::from("_dmarc.vitalik.eth".to_string());
+        let txt = DnsRecord { name: qname.clone(), rtype: 16, rclass: 1, ttl: 300, rdata: b"\x04v=DM\x04ARC1".to_vec() };
+        let a = DnsRecord { name: qname.clone(), rtype: 1, rclass: 1, ttl: 300, rdata: vec![1, 2, 3, 4] };
+        let data = [a.serialize(), txt.serialize()].concat();
+
+        let records = parse_rrset(&data, &qname, 16).unwrap();
+        assert_eq!(records, vec![txt]);
+        assert_eq!(txt_value(&records[0].rdata).unwrap(), "v=DMARC1");
+        assert_eq!(parse_rrset(&data[1..], &qname, 16), None);
+        assert_eq!(txt_value(b"\x05abc"), None);
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::dns::DnsName;
+
+/// Email authentication TXT records, served from the DNS profile of the name or from a text key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailAuthRecord {
+    /// `v=spf1` at the name itself
+    Spf,
+    /// `_dmarc.<name>`
+    Dmarc,
+    /// `<selector>._domainkey.<name>`
+    Dkim(String),
+}
+
+impl EmailAuthRecord {
+    /// Parses `_dmarc.<name>` and `<selector>._domainkey.<name>`, SPF has no prefix and is the
+    /// fallback for TXT queries of a bare name.
+    pub fn parse_query(qname: &DnsName) -> Option<(EmailAuthRecord, DnsName)> {
+        let labels = qname.labels.as_slice();
+        if let [first, rest @ ..] = labels {
+            if first.label.eq_ignore_ascii_case(b"_dmarc") && !rest.is_empty() {
+                return Some((EmailAuthRecord::Dmarc, DnsName { labels: rest.to_vec() }));
+            }
+        }
+        let position = labels.iter().position(|x| x.label.eq_ignore_ascii_case(b"_domainkey"))?;
+        if position == 0 || position + 1 == labels.len() {
+            return None;
+        }
+        let selector = labels[..position]
+            .iter()
+            .map(|x| String::from_utf8(x.label.clone()).ok())
+            .collect::<Option<Vec<String>>>()?
+            .join(".");
+        Some((EmailAuthRecord::Dkim(selector), DnsName { labels: labels[position + 1..].to_vec() }))
+    }
+
+    /// Text key the record is read from when the name has no DNS profile record for it.
+    pub fn text_key(&self) -> String {
+        match self {
+            EmailAuthRecord::Spf => "dns.spf".to_string(),
+            EmailAuthRecord::Dmarc => "dns.dmarc".to_string(),
+            EmailAuthRecord::Dkim(selector) => format!("dns.dkim.{}", selector),
+        }
+    }
+
+    /// Whether a TXT value is meant to be this record, SPF and DMARC share their owner name with other TXT records.
+    pub fn is_candidate(&self, value: &str) -> bool {
+        let value = value.trim_start().to_ascii_lowercase();
+        match self {
+            EmailAuthRecord::Spf => value == "v=spf1" || value.starts_with("v=spf1 "),
+            EmailAuthRecord::Dmarc => value.starts_with("v=dmarc1"),
+            EmailAuthRecord::Dkim(_) => true,
+        }
+    }
+
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match self {
+            EmailAuthRecord::Spf => validate_spf(value),
+            EmailAuthRecord::Dmarc => validate_dmarc(value),
+            EmailAuthRecord::Dkim(_) => validate_dkim(value),
+        }
+    }
+}
+
+fn validate_cidr(cidr: Option<&str>, max: u8) -> Result<(), String> {
+    match cidr.map(|x| x.parse::<u8>()) {
+        None => Ok(()),
+        Some(Ok(x)) if x <= max => Ok(()),
+        Some(_) => Err(format!("invalid prefix length {:?}", cidr)),
+    }
+}
+
+/// RFC 7208 record syntax: `v=spf1` followed by mechanisms and modifiers.
+fn validate_spf(value: &str) -> Result<(), String> {
+    let mut terms = value.split(' ').filter(|x| !x.is_empty());
+    if !terms.next().is_some_and(|x| x.eq_ignore_ascii_case("v=spf1")) {
+        return Err("missing v=spf1".to_string());
+    }
+    let mut modifiers = HashSet::new();
+    for term in terms {
+        if let Some((name, _)) = term.split_once('=').filter(|(name, _)| !name.contains(':') && !name.contains('/')) {
+            let valid_name = name.starts_with(|x: char| x.is_ascii_alphabetic())
+                && name.chars().all(|x| x.is_ascii_alphanumeric() || "-_.".contains(x));
+            if !valid_name {
+                return Err(format!("invalid modifier {}", term));
+            }
+            let name = name.to_ascii_lowercase();
+            if (name == "redirect" || name == "exp") && !modifiers.insert(name) {
+                return Err(format!("duplicate modifier {}", term));
+            }
+            continue;
+        }
+
+        let mechanism = term.trim_start_matches(['+', '-', '~', '?']);
+        if term.len() - mechanism.len() > 1 {
+            return Err(format!("invalid qualifier {}", term));
+        }
+        let (spec, cidr) = match mechanism.split_once('/') {
+            Some((spec, cidr)) => (spec, Some(cidr)),
+            None => (mechanism, None),
+        };
+        let (name, domain) = match spec.split_once(':') {
+            Some((name, domain)) => (name, Some(domain)),
+            None => (spec, None),
+        };
+        let valid = match name.to_ascii_lowercase().as_str() {
+            "all" => domain.is_none() && cidr.is_none(),
+            "include" | "exists" => domain.is_some_and(|x| !x.is_empty()) && cidr.is_none(),
+            "ptr" => domain != Some("") && cidr.is_none(),
+            "a" | "mx" => {
+                // a:<domain>/<ip4-cidr>//<ip6-cidr>, either length may be left out
+                let (cidr4, cidr6) = match cidr {
+                    Some(x) => match x.strip_prefix('/') {
+                        Some(cidr6) => (None, Some(cidr6)),
+                        None => x.split_once("//").map_or((Some(x), None), |(a, b)| (Some(a), Some(b))),
+                    },
+                    None => (None, None),
+                };
+                domain != Some("") && validate_cidr(cidr4, 32).is_ok() && validate_cidr(cidr6, 128).is_ok()
+            }
+            "ip4" => domain.is_some_and(|x| x.parse::<Ipv4Addr>().is_ok()) && validate_cidr(cidr, 32).is_ok(),
+            "ip6" => domain.is_some_and(|x| x.parse::<Ipv6Addr>().is_ok()) && validate_cidr(cidr, 128).is_ok(),
+            _ => false,
+        };
+        if !valid {
+            return Err(format!("invalid mechanism {}", term));
+        }
+    }
+    Ok(())
+}
+
+/// Splits a DKIM/DMARC `tag=value; tag=value` list, tags may not repeat.
+fn parse_tag_list(value: &str) -> Result<Vec<(String, &str)>, String> {
+    let mut tags: Vec<(String, &str)> = Vec::new();
+    for spec in value.split(';').map(str::trim).filter(|x| !x.is_empty()) {
+        let (tag, value) = spec.split_once('=').ok_or_else(|| format!("invalid tag {}", spec))?;
+        let tag = tag.trim().to_ascii_lowercase();
+        if tag.is_empty() || !tag.chars().all(|x| x.is_ascii_alphanumeric() || x == '_') {
+            return Err(format!("invalid tag {}", spec));
+        }
+        if tags.iter().any(|(x, _)| *x == tag) {
+            return Err(format!("duplicate tag {}", tag));
+        }
+        tags.push((tag, value.trim()));
+    }
+    Ok(tags)
+}
+
+/// RFC 7489 record syntax: `v=DMARC1; p=<policy>` followed by optional tags.
+fn validate_dmarc(value: &str) -> Result<(), String> {
+    let tags = parse_tag_list(value)?;
+    match tags.as_slice() {
+        [(v, version), (p, _), ..] if v == "v" && p == "p" && *version == "DMARC1" => {}
+        _ => return Err("must start with v=DMARC1; p=".to_string()),
+    }
+    for (tag, value) in &tags[1..] {
+        let valid = match tag.as_str() {
+            "p" | "sp" => ["none", "quarantine", "reject"].contains(&value.to_ascii_lowercase().as_str()),
+            "adkim" | "aspf" => ["r", "s"].contains(&value.to_ascii_lowercase().as_str()),
+            "pct" => value.parse::<u8>().is_ok_and(|x| x <= 100),
+            "ri" => value.parse::<u32>().is_ok(),
+            "rua" | "ruf" => value.split(',').all(|x| x.trim().to_ascii_lowercase().starts_with("mailto:")),
+            "fo" => value.split(':').all(|x| ["0", "1", "d", "s"].contains(&x.trim())),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("invalid {}={}", tag, value));
+        }
+    }
+    Ok(())
+}
+
+/// RFC 6376 key record syntax: optional leading `v=DKIM1` and a base64 `p=` public key,
+/// an empty `p=` revokes the key.
+fn validate_dkim(value: &str) -> Result<(), String> {
+    let tags = parse_tag_list(value)?;
+    if let Some(i) = tags.iter().position(|(tag, _)| tag == "v") {
+        if i != 0 || tags[i].1 != "DKIM1" {
+            return Err("v=DKIM1 must be the first tag".to_string());
+        }
+    }
+    for (tag, value) in &tags {
+        let valid = match tag.as_str() {
+            "p" => {
+                let key: String = value.chars().filter(|x| !x.is_whitespace()).collect();
+                STANDARD.decode(key).is_ok()
+            }
+            "k" => ["rsa", "ed25519"].contains(&value.to_ascii_lowercase().as_str()),
+            "h" => value.split(':').all(|x| ["sha1", "sha256"].contains(&x.trim().to_ascii_lowercase().as_str())),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("invalid {}={}", tag, value));
+        }
+    }
+    if !tags.iter().any(|(tag, _)| tag == "p") {
+        return Err("missing p=".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let name = DnsName::from("vitalik.eth".to_string());
+        assert_eq!(
+            EmailAuthRecord::parse_query(&DnsName::from("_dmarc.vitalik.eth".to_string())),
+            Some((EmailAuthRecord::Dmarc, name.clone()))
+        );
+        assert_eq!(
+            EmailAuthRecord::parse_query(&DnsName::from("s1.mail._domainkey.vitalik.eth".to_string())),
+            Some((EmailAuthRecord::Dkim("s1.mail".to_string()), name))
+        );
+        assert_eq!(EmailAuthRecord::parse_query(&DnsName::from("_domainkey.vitalik.eth".to_string())), None);
+        assert_eq!(EmailAuthRecord::parse_query(&DnsName::from("vitalik.eth".to_string())), None);
+    }
+
+    #[test]
+    fn test_validate_spf() {
+        assert!(validate_spf("v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 a mx:mail.example/24//64 include:_spf.example ~all").is_ok());
+        assert!(validate_spf("v=spf1 redirect=_spf.example").is_ok());
+        assert!(validate_spf("v=spf1 ip4:192.0.2.300 -all").is_err());
+        assert!(validate_spf("v=spf1 include -all").is_err());
+        assert!(validate_spf("v=spf1 redirect=a redirect=b").is_err());
+        assert!(validate_spf("spf1 -all").is_err());
+    }
+
+    #[test]
+    fn test_validate_dmarc_dkim() {
+        assert!(validate_dmarc("v=DMARC1; p=reject; rua=mailto:dmarc@example.com; pct=100").is_ok());
+        assert!(validate_dmarc("v=DMARC1; p=deny").is_err());
+        assert!(validate_dmarc("p=reject; v=DMARC1").is_err());
+        assert!(validate_dmarc("v=DMARC1; p=none; pct=101").is_err());
+
+        assert!(validate_dkim("v=DKIM1; k=rsa; p=MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQ==").is_ok());
+        assert!(validate_dkim("v=DKIM1; p=").is_ok());
+        assert!(validate_dkim("k=rsa; v=DKIM1; p=").is_err());
+        assert!(validate_dkim("v=DKIM1; p=not base64!").is_err());
+        assert!(validate_dkim("v=DKIM1; k=rsa").is_err());
+    }
+}
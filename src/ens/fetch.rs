@@ -0,0 +1,130 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use url::{Host, Url};
+
+/// Largest response body read from a URL taken from chain data.
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// Whether `host` is one of `allowlist` or a subdomain of one.
+fn is_allowlisted(allowlist: &[String], host: &str) -> bool {
+    allowlist.iter().any(|x| host == x || host.ends_with(&format!(".{}", x)))
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || a >= 240
+        || (a == 100 && (64..128).contains(&b)) // shared address space
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (b == 18 || b == 19))) // benchmarking
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(ipv4);
+    }
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        // NAT64 of an IPv4 address
+        let [.., a, b, c, d] = ip.octets();
+        return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00 // unique local
+        || (segments[0] & 0xffc0) == 0xfe80 // link local
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8)) // documentation
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(x) => is_public_ipv4(x),
+        IpAddr::V6(x) => is_public_ipv6(x),
+    }
+}
+
+/// A client for a URL taken from chain data, which whoever deploys a contract controls.
+/// Allowlisted hosts may be fetched over http or https. Any other URL must be https to a host
+/// resolving only to public addresses, the client connects to the addresses checked here so the
+/// host cannot resolve elsewhere by the time of the request. Redirects are not followed.
+pub async fn client_for(url: &Url, allowlist: &[String], timeout: Duration) -> Result<reqwest::Client, String> {
+    let host = url.host().ok_or_else(|| format!("url without host {}", url))?;
+    let builder = reqwest::Client::builder().timeout(timeout).redirect(reqwest::redirect::Policy::none());
+    if (url.scheme() == "https" || url.scheme() == "http") && is_allowlisted(allowlist, &host.to_string()) {
+        return builder.build().map_err(|e| e.to_string());
+    }
+    if url.scheme() != "https" {
+        return Err(format!("url not allowed {}", url));
+    }
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = match host {
+        Host::Ipv4(x) => vec![SocketAddr::new(x.into(), port)],
+        Host::Ipv6(x) => vec![SocketAddr::new(x.into(), port)],
+        Host::Domain(x) => tokio::net::lookup_host((x, port)).await.map_err(|e| e.to_string())?.collect(),
+    };
+    if addrs.is_empty() || !addrs.iter().all(|x| is_public(x.ip())) {
+        return Err(format!("url not allowed {}", url));
+    }
+    let builder = match host {
+        Host::Domain(x) => builder.resolve_to_addrs(x, &addrs),
+        _ => builder,
+    };
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Reads the response body, failing once it is larger than `MAX_BODY_SIZE`.
+pub async fn read_body(mut response: reqwest::Response) -> Result<Vec<u8>, String> {
+    if response.content_length().is_some_and(|x| x > MAX_BODY_SIZE as u64) {
+        return Err("response body too large".to_string());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err("response body too large".to_string());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::1", "fd00::1", "fe80::1", "::ffff:10.0.0.1", "64:ff9b::a9fe:a9fe"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public("1.1.1.1".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_client_for() {
+        let url = |x: &str| Url::parse(x).unwrap();
+        assert!(client_for(&url("http://127.0.0.1/x"), &[], TIMEOUT).await.is_err());
+        assert!(client_for(&url("https://127.0.0.1/x"), &[], TIMEOUT).await.is_err());
+        assert!(client_for(&url("https://[::1]/x"), &[], TIMEOUT).await.is_err());
+        assert!(client_for(&url("https://localhost/x"), &[], TIMEOUT).await.is_err());
+        assert!(client_for(&url("http://1.1.1.1/x"), &[], TIMEOUT).await.is_err());
+        assert!(client_for(&url("https://1.1.1.1/x"), &[], TIMEOUT).await.is_ok());
+        assert!(client_for(&url("file:///etc/passwd"), &["localhost".to_string()], TIMEOUT).await.is_err());
+        assert!(client_for(&url("http://127.0.0.1/x"), &["127.0.0.1".to_string()], TIMEOUT).await.is_ok());
+    }
+}
@@ -0,0 +1,120 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ethers::utils::hex;
+use sha2::{Digest, Sha256};
+
+use crate::dns::keys::{OPENPGPKEY, SSHFP, TLSA};
+use crate::dns::DnsName;
+
+/// DANE and key records served from the DNS profile of the name or from a text key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyRecord {
+    /// `_<port>._<protocol>.<name>`
+    Tlsa { port: u16, protocol: String },
+    /// `<name>`
+    Sshfp,
+    /// `<sha256(local part)>._openpgpkey.<name>`
+    OpenPgpKey { hash: String },
+}
+
+impl KeyRecord {
+    pub fn parse_query(qname: &DnsName, qtype: u16) -> Option<(KeyRecord, DnsName)> {
+        let labels = qname.labels.as_slice();
+        match (qtype, labels) {
+            (TLSA, [port, protocol, rest @ ..]) if !rest.is_empty() => {
+                let port = std::str::from_utf8(port.label.strip_prefix(b"_")?).ok()?.parse().ok()?;
+                let protocol = std::str::from_utf8(protocol.label.strip_prefix(b"_")?).ok()?.to_ascii_lowercase();
+                ["tcp", "udp", "sctp"]
+                    .contains(&protocol.as_str())
+                    .then(|| (KeyRecord::Tlsa { port, protocol }, DnsName { labels: rest.to_vec() }))
+            }
+            (SSHFP, [_, ..]) => Some((KeyRecord::Sshfp, qname.clone())),
+            (OPENPGPKEY, [hash, openpgpkey, rest @ ..]) if openpgpkey.label.eq_ignore_ascii_case(b"_openpgpkey") && !rest.is_empty() => {
+                let hash = String::from_utf8(hash.label.to_ascii_lowercase()).ok()?;
+                Some((KeyRecord::OpenPgpKey { hash }, DnsName { labels: rest.to_vec() }))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn rtype(&self) -> u16 {
+        match self {
+            KeyRecord::Tlsa { .. } => TLSA,
+            KeyRecord::Sshfp => SSHFP,
+            KeyRecord::OpenPgpKey { .. } => OPENPGPKEY,
+        }
+    }
+
+    /// Text key holding the records in presentation format when the name has no DNS profile record for them.
+    pub fn text_key(&self) -> String {
+        match self {
+            KeyRecord::Tlsa { port, protocol } => format!("dns.tlsa.{}.{}", port, protocol),
+            KeyRecord::Sshfp => "dns.sshfp".to_string(),
+            KeyRecord::OpenPgpKey { hash } => format!("dns.openpgpkey.{}", hash),
+        }
+    }
+}
+
+/// Text key holding OpenSSH public keys (`ssh-ed25519 AAAA... comment`, one per line) whose
+/// SSHFP records are computed when `dns.sshfp` is not set.
+pub const SSH_PUBLIC_KEYS_TEXT_KEY: &str = "dns.sshkey";
+
+/// SHA-256 SSHFP record of an OpenSSH public key line.
+fn sshfp_of_public_key(line: &str) -> Option<String> {
+    let mut fields = line.split_whitespace();
+    let key_type = fields.next()?;
+    let blob = STANDARD.decode(fields.next()?).ok()?;
+    // the key blob starts with its own type as an ssh string
+    let len = u32::from_be_bytes(blob.get(0..4)?.try_into().ok()?) as usize;
+    if blob.get(4..4 + len)? != key_type.as_bytes() {
+        return None;
+    }
+    let algorithm = match key_type {
+        "ssh-rsa" => 1,
+        "ssh-dss" => 2,
+        x if x.starts_with("ecdsa-sha2-") => 3,
+        "ssh-ed25519" => 4,
+        "ssh-ed448" => 6,
+        _ => return None,
+    };
+    Some(format!("{} 2 {}", algorithm, hex::encode(Sha256::digest(&blob))))
+}
+
+/// SSHFP records of the OpenSSH public keys stored under `dns.sshkey`, `None` when any key is malformed.
+pub fn sshfp_of_public_keys(keys: &str) -> Option<String> {
+    let records = keys
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(sshfp_of_public_key)
+        .collect::<Option<Vec<String>>>()?;
+    (!records.is_empty()).then(|| records.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let name = DnsName::from("vitalik.eth".to_string());
+        assert_eq!(
+            KeyRecord::parse_query(&DnsName::from("_443._tcp.vitalik.eth".to_string()), TLSA),
+            Some((KeyRecord::Tlsa { port: 443, protocol: "tcp".to_string() }, name.clone()))
+        );
+        assert_eq!(KeyRecord::parse_query(&DnsName::from("_443._ftp.vitalik.eth".to_string()), TLSA), None);
+        assert_eq!(KeyRecord::parse_query(&name, SSHFP), Some((KeyRecord::Sshfp, name.clone())));
+        assert_eq!(
+            KeyRecord::parse_query(&DnsName::from("c93f1e40._openpgpkey.vitalik.eth".to_string()), OPENPGPKEY),
+            Some((KeyRecord::OpenPgpKey { hash: "c93f1e40".to_string() }, name))
+        );
+    }
+
+    #[test]
+    fn test_sshfp_of_public_keys() {
+        let blob = [&11u32.to_be_bytes()[..], b"ssh-ed25519", &32u32.to_be_bytes(), &[7; 32]].concat();
+        let key = format!("ssh-ed25519 {} user@host", STANDARD.encode(&blob));
+        assert_eq!(sshfp_of_public_keys(&key).unwrap(), format!("4 2 {}", hex::encode(Sha256::digest(&blob))));
+        assert_eq!(sshfp_of_public_keys(&format!("ssh-rsa {}", STANDARD.encode(&blob))), None);
+        assert_eq!(sshfp_of_public_keys(""), None);
+    }
+}
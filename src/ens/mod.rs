@@ -0,0 +1,1250 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ethers::abi::{self, ParamType, Token};
+use ethers::prelude::*;
+
+use crate::dns::{self, DnsError, DnsName, DnsRecord};
+use crate::light_client::LightClient;
+
+use self::avatar::{AvatarReference, NftStandard};
+use self::block::{BlockConfig, PinnedBlock};
+use self::cache::Record;
+use self::ccip::OffchainLookup;
+use self::email::EmailAuthRecord;
+use self::keys::KeyRecord;
+use self::profiles::ProfileRecord;
+pub(crate) use self::avatar::AvatarConfig;
+pub(crate) use self::cache::AnswerCache;
+pub(crate) use self::ccip::CcipConfig;
+pub(crate) use self::text_records::TextRecordServices;
+pub(crate) use self::universal::ResolverBackend;
+
+mod atproto;
+mod avatar;
+pub(crate) mod block;
+mod cache;
+mod ccip;
+pub(crate) mod chain;
+mod coins;
+mod contenthash;
+mod dns_profile;
+mod email;
+mod fetch;
+mod keys;
+mod profiles;
+pub(crate) mod proof;
+mod text_records;
+pub(crate) mod universal;
+
+pub(crate) struct EthersAnswerProvider<T: Send + Sync> {
+    pub provider: ethers::providers::Provider<T>,
+    pub text_records: TextRecordServices,
+    pub avatar: AvatarConfig,
+    pub ccip: CcipConfig,
+    pub backend: ResolverBackend,
+    /// ENS registry of the configured chain.
+    pub registry: Address,
+    /// The registry falls back to the old registry for names without a record of their own.
+    pub registry_fallback: bool,
+    /// Block each question is resolved at.
+    pub block: BlockConfig,
+    /// Serve only records read from storage proofs verified against the block's state root.
+    pub verify_proofs: bool,
+    /// PublicResolver deployments, records are only verified on resolvers running their code.
+    pub public_resolvers: Vec<Address>,
+    /// Resolve at the light client's finalized block instead of `block`.
+    pub light_client: Option<Arc<LightClient>>,
+    /// Answers kept until the logs of the registry or resolver change the records they read.
+    pub cache: Option<AnswerCache>,
+}
+
+/// How often registry and resolver logs are polled for changes of cached answers.
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(12);
+/// Most blocks of logs applied at once, the cache starts over after longer gaps.
+const MAX_LOG_RANGE: u64 = 1000;
+
+/// Text key holding CAA records in presentation format, one per line.
+const CAA_TEXT_KEY: &str = "dns.caa";
+
+/// Parses `<hexaddr>.addr.reverse` into the address it is the reverse record of.
+fn parse_reverse_address(qname: &DnsName) -> Option<Address> {
+    let [addr, reverse_label, tld] = qname.labels.as_slice() else {
+        return None;
+    };
+    if !reverse_label.label.eq_ignore_ascii_case(b"addr") || !tld.label.eq_ignore_ascii_case(b"reverse") || addr.label.len() != 40 {
+        return None;
+    }
+    std::str::from_utf8(&addr.label).ok()?.parse::<Address>().ok()
+}
+
+/// The ENS name a query is about, with any service or coin labels removed.
+fn ens_name_of(qname: &DnsName, text_records: &TextRecordServices) -> DnsName {
+    if let Some(name) = avatar::parse_query(qname).or_else(|| atproto::parse_query(qname)) {
+        return name;
+    }
+    if let Some((_, name)) = EmailAuthRecord::parse_query(qname) {
+        return name;
+    }
+    if let Some((_, name)) = KeyRecord::parse_query(qname, dns::keys::TLSA).or_else(|| KeyRecord::parse_query(qname, dns::keys::OPENPGPKEY)) {
+        return name;
+    }
+    if let Some((_, name)) = ProfileRecord::parse_query(qname) {
+        return name;
+    }
+    if let Some((_, name)) = coins::parse_coin_query(qname) {
+        return name;
+    }
+    text_records
+        .parse_query(qname)
+        .map(|(_, name)| name)
+        .unwrap_or_else(|| qname.clone())
+}
+
+/// ENSIP-10 `IExtendedResolver` interface id, `resolve(bytes,bytes)`.
+const EXTENDED_RESOLVER_INTERFACE: [u8; 4] = [0x90, 0x61, 0xb9, 0x23];
+
+/// DNS wire format of an ENS name as passed to `resolve(bytes,bytes)`, labels are kept as UTF-8.
+fn dns_encode(name: &str) -> Option<Vec<u8>> {
+    let mut encoded = Vec::new();
+    for label in name.split('.').filter(|x| !x.is_empty()) {
+        encoded.push(u8::try_from(label.len()).ok()?);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    Some(encoded)
+}
+
+/// Decodes return data consisting of a single value of type `param`.
+fn decode_single(param: ParamType, data: &[u8]) -> Result<Token, DnsError> {
+    abi::decode(&[param], data)
+        .ok()
+        .and_then(|x| x.into_iter().next())
+        .ok_or(DnsError::InvalidResolverResponse)
+}
+
+impl<T: Send + Sync + JsonRpcClient> EthersAnswerProvider<T> {
+    /// The block a question is resolved against, `confirmations` below the block at the tag.
+    async fn pin_block(&self) -> Result<PinnedBlock, DnsError> {
+        if let Some(light_client) = &self.light_client {
+            let header = light_client.fresh_finalized_execution().ok_or(DnsError::StaleFinalizedBlock(light_client.finalized_slot()))?;
+            return Ok(PinnedBlock { number: header.block_number.into(), hash: header.block_hash, state_root: header.state_root });
+        }
+        let unavailable = || DnsError::BlockUnavailable(self.block.to_string());
+        let mut block = self.provider.get_block(self.block.tag).await?.ok_or_else(unavailable)?;
+        if self.block.confirmations > 0 {
+            let number = block.number.ok_or_else(unavailable)?;
+            let confirmed = number.checked_sub(self.block.confirmations.into()).ok_or_else(unavailable)?;
+            block = self.provider.get_block(confirmed).await?.ok_or_else(unavailable)?;
+        }
+        Ok(PinnedBlock {
+            number: block.number.ok_or_else(unavailable)?,
+            hash: block.hash.ok_or_else(unavailable)?,
+            state_root: block.state_root,
+        })
+    }
+
+    async fn call_contract(&self, to: Address, signature: &str, params: &[Token]) -> Result<Bytes, DnsError> {
+        self.ccip_call(to, [&ethers::utils::id(signature)[..], &abi::encode(params)].concat().into()).await
+    }
+
+    /// `eth_call` that follows EIP-3668 OffchainLookup reverts through the gateways they name and
+    /// returns the result of the final callback, which verifies the gateway response on chain.
+    async fn ccip_call(&self, to: Address, mut data: Bytes) -> Result<Bytes, DnsError> {
+        if self.verify_proofs {
+            return Err(DnsError::UnverifiableRecord(format!("eth_call to {:?}", to)));
+        }
+        let mut lookups = 0;
+        loop {
+            let tx = TransactionRequest::new().to(to).data(data);
+            let error = match self.provider.call(&tx.into(), block::current()).await {
+                Ok(result) => return Ok(result),
+                Err(e) => e,
+            };
+            let Some(lookup) = ccip::revert_data(&error).and_then(|x| OffchainLookup::decode(&x)) else {
+                return Err(error.into());
+            };
+            if lookup.sender != to {
+                return Err(DnsError::CcipReadError(format!("OffchainLookup sender {:?} is not {:?}", lookup.sender, to)));
+            }
+            if lookups == self.ccip.max_redirects {
+                return Err(DnsError::CcipReadError(format!("more than {} OffchainLookup redirects", lookups)));
+            }
+            lookups += 1;
+            // gateway answers change without logs
+            cache::uncacheable();
+
+            let response = self.ccip.fetch(&lookup).await?;
+            data = lookup.callback_data(&response);
+        }
+    }
+
+    /// ENSIP-10: the resolver of `name`, or of its closest ancestor that has one, and whether
+    /// it was set on `name` itself.
+    async fn find_resolver(&self, name: &str) -> Result<(Address, bool), DnsError> {
+        let mut current = name;
+        loop {
+            cache::read(ens::namehash(current), Record::Resolver);
+            let data = self.provider.call(&ens::get_resolver(self.registry, current).into(), block::current()).await?;
+            if data.len() >= 32 {
+                let resolver = Address::from_slice(&data[12..32]);
+                if !resolver.is_zero() {
+                    return Ok((resolver, current.len() == name.len()));
+                }
+            }
+            match current.split_once('.') {
+                Some((_, parent)) => current = parent,
+                None => return Err(DnsError::EnsResolverNotFound(name.to_string())),
+            }
+        }
+    }
+
+    /// Whether the registry has an owner for `name`, errors count as one.
+    async fn has_owner(&self, name: &str) -> bool {
+        let tx = TransactionRequest::new()
+            .to(self.registry)
+            .data([&ethers::utils::id("owner(bytes32)")[..], ens::namehash(name).as_bytes()].concat());
+        match self.provider.call(&tx.into(), block::current()).await {
+            Ok(data) => data.iter().any(|x| *x != 0),
+            Err(e) => {
+                println!("error reading owner of {} {:?}", name, e);
+                true
+            }
+        }
+    }
+
+    /// ERC-165 `supportsInterface`, resolvers that revert or return garbage support nothing.
+    async fn supports_interface(&self, contract: Address, interface_id: [u8; 4]) -> bool {
+        self.call_contract(contract, "supportsInterface(bytes4)", &[Token::FixedBytes(interface_id.to_vec())])
+            .await
+            .ok()
+            .and_then(|data| decode_single(ParamType::Bool, &data).ok())
+            .and_then(Token::into_bool)
+            .unwrap_or(false)
+    }
+
+    /// Calls `signature` on the resolver of `name`, with the name's node as the first argument
+    /// followed by `params`. Extended resolvers are called through `resolve(bytes,bytes)`, which
+    /// is also how names without a resolver of their own resolve through a wildcard ancestor.
+    async fn call_resolver(&self, name: &str, signature: &str, params: Vec<Token>) -> Result<Bytes, DnsError> {
+        match cache::record_of_call(signature, &params) {
+            Some(record) => cache::read(ens::namehash(name), record),
+            None => cache::uncacheable(),
+        }
+        let mut tokens = vec![Token::FixedBytes(ens::namehash(name).as_bytes().to_vec())];
+        tokens.extend(params);
+        let call_data = [&ethers::utils::id(signature)[..], &abi::encode(&tokens)].concat();
+        if self.verify_proofs {
+            return self.call_resolver_verified(name, signature, &tokens).await;
+        }
+        if let ResolverBackend::UniversalResolver(universal_resolver) = self.backend {
+            // which resolver answered is not known
+            cache::uncacheable();
+            return self.call_universal_resolver(universal_resolver, name, call_data).await;
+        }
+
+        let (resolver, exact) = self.find_resolver(name).await?;
+        if !exact {
+            // wildcard resolvers answer for names they log no changes of
+            cache::uncacheable();
+        }
+        if self.supports_interface(resolver, EXTENDED_RESOLVER_INTERFACE).await {
+            // extended resolvers may answer from anything, not only the records they log changes of
+            cache::uncacheable();
+            let dns_name = dns_encode(name).ok_or_else(|| DnsError::EnsResolverNotFound(name.to_string()))?;
+            let data = self
+                .call_contract(resolver, "resolve(bytes,bytes)", &[Token::Bytes(dns_name), Token::Bytes(call_data)])
+                .await?;
+            return decode_single(ParamType::Bytes, &data)?
+                .into_bytes()
+                .map(Bytes::from)
+                .ok_or(DnsError::InvalidResolverResponse);
+        }
+        if !exact {
+            return Err(DnsError::EnsResolverNotFound(name.to_string()));
+        }
+        self.ccip_call(resolver, call_data.into()).await
+    }
+
+    /// Resolves `call_data` with a single `resolve(bytes,bytes)` on the UniversalResolver, which
+    /// finds the (wildcard) resolver and follows its OffchainLookup reverts through `ccip_call`.
+    async fn call_universal_resolver(&self, universal_resolver: Address, name: &str, call_data: Vec<u8>) -> Result<Bytes, DnsError> {
+        let dns_name = dns_encode(name).ok_or_else(|| DnsError::EnsResolverNotFound(name.to_string()))?;
+        let data = self
+            .call_contract(universal_resolver, "resolve(bytes,bytes)", &[Token::Bytes(dns_name), Token::Bytes(call_data)])
+            .await
+            .map_err(|e| if universal::is_resolver_not_found(&e) { DnsError::EnsResolverNotFound(name.to_string()) } else { e })?;
+        // (bytes result, address resolver)
+        abi::decode(&[ParamType::Bytes, ParamType::Address], &data)
+            .ok()
+            .and_then(|x| x.into_iter().next())
+            .and_then(Token::into_bytes)
+            .map(Bytes::from)
+            .ok_or(DnsError::InvalidResolverResponse)
+    }
+
+    /// Storage of `address` at `slots`, from an `eth_getProof` verified against the pinned block.
+    async fn get_verified_storage(&self, address: Address, slots: &[H256]) -> Result<Vec<U256>, DnsError> {
+        Ok(self.get_verified_account(address, slots).await?.1)
+    }
+
+    /// `get_verified_storage` with the account's code hash, `None` when it has no code.
+    async fn get_verified_account(&self, address: Address, slots: &[H256]) -> Result<(Option<H256>, Vec<U256>), DnsError> {
+        let block = block::pinned().ok_or_else(|| DnsError::InvalidProof("no pinned block".to_string()))?;
+        let response = self.provider.get_proof(address, slots.to_vec(), Some(block.id())).await?;
+        if response.address != address {
+            return Err(DnsError::InvalidProof(format!("proof of {:?} instead of {:?}", response.address, address)));
+        }
+        let code_hash = proof::verify_code_hash(block.state_root, &response)?;
+        Ok((code_hash, proof::verify_storage(block.state_root, &response, slots)?))
+    }
+
+    /// Whether `resolver` is one of the PublicResolver deployments or runs the same code, the only
+    /// storage layout verified records are read with.
+    async fn is_public_resolver(&self, resolver: Address, code_hash: Option<H256>) -> Result<bool, DnsError> {
+        let Some(code_hash) = code_hash else {
+            return Ok(false);
+        };
+        if self.public_resolvers.contains(&resolver) {
+            return Ok(true);
+        }
+        for deployment in &self.public_resolvers {
+            if self.get_verified_account(*deployment, &[]).await?.0 == Some(code_hash) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reads a `bytes` or `string` stored at `slot` of `address`.
+    async fn get_verified_bytes(&self, address: Address, slot: H256) -> Result<Vec<u8>, DnsError> {
+        let header = self.get_verified_storage(address, &[slot]).await?[0];
+        match proof::stored_bytes(slot, header)? {
+            proof::StoredBytes::Short(value) => Ok(value),
+            proof::StoredBytes::Long { len, slots } => Ok(proof::join_stored_bytes(len, &self.get_verified_storage(address, &slots).await?)),
+        }
+    }
+
+    /// `call_resolver` answered from the registry and PublicResolver storage instead of `eth_call`.
+    /// Only names with a resolver of their own running the code of a PublicResolver deployment can
+    /// be verified, anything else is not served.
+    async fn call_resolver_verified(&self, name: &str, signature: &str, tokens: &[Token]) -> Result<Bytes, DnsError> {
+        let (field, kind) = proof::resolver_field(signature).ok_or_else(|| DnsError::UnverifiableRecord(signature.to_string()))?;
+        let mut keys = tokens
+            .iter()
+            .map(proof::mapping_key)
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| DnsError::UnverifiableRecord(signature.to_string()))?;
+        if signature == "addr(bytes32)" {
+            // the ETH address is stored as coin type 60
+            keys.push(H256::from_low_u64_be(60).as_bytes().to_vec());
+        }
+
+        let node = ens::namehash(name);
+        cache::read(node, Record::Resolver);
+        let slots = [proof::registry_owner_slot(node), proof::registry_resolver_slot(node)];
+        let [owner, resolver] = self.get_verified_storage(self.registry, &slots).await?[..] else {
+            return Err(DnsError::InvalidProof("missing registry slots".to_string()));
+        };
+        if owner.is_zero() && self.registry_fallback {
+            // the old registry's storage layout is not known, its records cannot be verified
+            return Err(DnsError::UnverifiableRecord(format!("{} is not in the registry, possibly in the old registry", name)));
+        }
+        let resolver = Address::from(proof::word(resolver));
+        if resolver.is_zero() {
+            return Err(DnsError::EnsResolverNotFound(name.to_string()));
+        }
+        let (code_hash, version) = self.get_verified_account(resolver, &[proof::record_version_slot(node)]).await?;
+        if !self.is_public_resolver(resolver, code_hash).await? {
+            return Err(DnsError::UnverifiableRecord(format!("resolver {:?} does not run PublicResolver code", resolver)));
+        }
+        let version = version[0];
+        let slot = proof::resolver_field_slot(field, version.low_u64(), &keys);
+        let value = self.get_verified_bytes(resolver, slot).await?;
+        Ok(abi::encode(&[kind.token(value)]).into())
+    }
+
+    /// Reads the text record `key` of `name`, empty when it is not set.
+    async fn get_text(&self, name: &str, key: &str) -> Result<String, DnsError> {
+        let data = self.call_resolver(name, "text(bytes32,string)", vec![Token::String(key.to_string())]).await?;
+        decode_single(ParamType::String, &data)?.into_string().ok_or(DnsError::InvalidResolverResponse)
+    }
+
+    /// The primary ENS name of `address`, only returned when the forward `addr` of that name
+    /// resolves back to the address.
+    async fn lookup_primary_name(&self, address: Address) -> Result<String, DnsError> {
+        let reverse_name = format!("{}.addr.reverse", ethers::utils::hex::encode(address));
+        let data = self.call_resolver(&reverse_name, "name(bytes32)", vec![]).await?;
+        let name = decode_single(ParamType::String, &data)?.into_string().ok_or(DnsError::InvalidResolverResponse)?;
+        if name.is_empty() {
+            return Ok(name);
+        }
+        let data = self.call_resolver(&name, ProfileRecord::Eth.signature(), vec![]).await?;
+        let forward = decode_single(ParamType::Address, &data)?.into_address().ok_or(DnsError::InvalidResolverResponse)?;
+        Ok(if forward == address { name } else { String::new() })
+    }
+
+    /// Resolves the forward verified primary ENS name of an address.
+    async fn get_reverse_answer_async(&self, qname: &DnsName) -> Option<String> {
+        let address = parse_reverse_address(qname)?;
+        match self.lookup_primary_name(address).await {
+            Ok(name) if !name.is_empty() => Some(name),
+            Ok(_) => None,
+            Err(e) => {
+                println!("error reverse resolving {:?} {:?}", address, e);
+                None
+            }
+        }
+    }
+
+    /// Resolves one of the standard resolver profiles that are rendered as TXT.
+    async fn get_profile_answer_async(&self, record: ProfileRecord, name: &DnsName) -> Option<String> {
+        let name = name.punycode_decode()?;
+        match self.call_resolver(&name, record.signature(), record.params()).await {
+            Ok(data) => record.format(&data),
+            Err(e) => {
+                println!("error resolving {:?} of {:?} {:?}", record, name, e);
+                None
+            }
+        }
+    }
+
+    /// Resolves an ENSIP-9 multicoin address and renders it in the chain's native format.
+    async fn get_coin_answer_async(&self, coin_type: u64, name: &DnsName) -> Option<String> {
+        let name = name.punycode_decode()?;
+        let res = self
+            .call_resolver(&name, "addr(bytes32,uint256)", vec![Token::Uint(coin_type.into())])
+            .await
+            .and_then(|data| decode_single(ParamType::Bytes, &data)?.into_bytes().ok_or(DnsError::InvalidResolverResponse));
+        match res {
+            Ok(data) if data.is_empty() => None,
+            Ok(data) => {
+                let address = coins::format_address(coin_type, &data);
+                if address.is_none() {
+                    println!("error formatting coin type {} address of {:?} {:?}", coin_type, name, data);
+                }
+                address
+            }
+            Err(e) => {
+                println!("error resolving coin type {} of {:?} {:?}", coin_type, name, e);
+                None
+            }
+        }
+    }
+
+    /// Resolves the ENSIP-12 `avatar` text record of `name` to the URI of the image. NFT references
+    /// are only followed on the provider's chain and when the NFT is owned by the name's ETH address.
+    async fn resolve_avatar_uri(&self, name: &str) -> Result<Option<String>, DnsError> {
+        let avatar = self.get_text(name, "avatar").await?;
+        let (chain_id, standard, contract, token_id) = match AvatarReference::parse(&avatar) {
+            None => return Ok(None),
+            Some(AvatarReference::Uri(uri)) => return Ok(Some(self.avatar.gateway_uri(&uri))),
+            Some(AvatarReference::Nft { chain_id, standard, contract, token_id }) => (chain_id, standard, contract, token_id),
+        };
+        // NFT ownership and metadata change without resolver logs
+        cache::uncacheable();
+        if self.provider.get_chainid().await? != chain_id.into() {
+            return Err(DnsError::AvatarChainUnsupported(chain_id));
+        }
+
+        let data = self.call_resolver(name, ProfileRecord::Eth.signature(), vec![]).await?;
+        let owner = decode_single(ParamType::Address, &data)?.into_address().ok_or(DnsError::InvalidResolverResponse)?;
+        let (owned, uri_signature) = match standard {
+            NftStandard::Erc721 => {
+                let data = self.call_contract(contract, "ownerOf(uint256)", &[Token::Uint(token_id)]).await?;
+                (decode_single(ParamType::Address, &data)?.into_address() == Some(owner), "tokenURI(uint256)")
+            }
+            NftStandard::Erc1155 => {
+                let data = self.call_contract(contract, "balanceOf(address,uint256)", &[Token::Address(owner), Token::Uint(token_id)]).await?;
+                (decode_single(ParamType::Uint(256), &data)?.into_uint().is_some_and(|x| !x.is_zero()), "uri(uint256)")
+            }
+        };
+        if owner.is_zero() || !owned {
+            return Err(DnsError::AvatarNotOwned);
+        }
+
+        let data = self.call_contract(contract, uri_signature, &[Token::Uint(token_id)]).await?;
+        let token_uri = decode_single(ParamType::String, &data)?.into_string().ok_or(DnsError::InvalidResolverResponse)?;
+        let metadata = avatar::fetch_metadata(&self.avatar, &avatar::expand_token_uri(&token_uri, standard, token_id)).await?;
+        Ok(avatar::metadata_image(&metadata).map(|x| self.avatar.gateway_uri(&x)))
+    }
+
+    /// Resource records of type `rtype` at `qname` from the EIP-1185 DNS profile of the ENS name `name`.
+    async fn get_dns_profile_records(&self, qname: &DnsName, name: &str, rtype: u16) -> Result<Vec<DnsRecord>, DnsError> {
+        let params = vec![Token::FixedBytes(dns_profile::name_hash(qname).to_vec()), Token::Uint(rtype.into())];
+        let data = self.call_resolver(name, "dnsRecord(bytes32,bytes32,uint16)", params).await?;
+        let rrset = decode_single(ParamType::Bytes, &data)?.into_bytes().ok_or(DnsError::InvalidResolverResponse)?;
+        dns_profile::parse_rrset(&rrset, qname, rtype).ok_or(DnsError::InvalidResolverResponse)
+    }
+
+    /// Resolves SPF, DMARC or DKIM from the DNS profile TXT records of `qname`, falling back to
+    /// the record's text key, and only answers when the stored value is syntactically valid.
+    async fn get_email_auth_answer_async(&self, record: EmailAuthRecord, qname: &DnsName, name: &DnsName) -> Option<String> {
+        let name = name.punycode_decode()?;
+        let from_profile = match self.get_dns_profile_records(qname, &name, 16).await {
+            Ok(records) => records
+                .iter()
+                .filter_map(|x| dns_profile::txt_value(&x.rdata))
+                .find(|x| record.is_candidate(x)),
+            Err(e) => {
+                println!("error resolving dns profile of {:?} {:?}", qname, e);
+                None
+            }
+        };
+        let value = match from_profile {
+            Some(value) => value,
+            None => match self.get_text(&name, &record.text_key()).await {
+                Ok(value) if !value.is_empty() => value,
+                Ok(_) => return None,
+                Err(e) => {
+                    println!("error resolving {:?} of {:?} {:?}", record, name, e);
+                    return None;
+                }
+            },
+        };
+        match record.validate(&value) {
+            Ok(()) => Some(value),
+            Err(e) => {
+                println!("error resolving {:?} of {:?} {:?}", record, name, DnsError::InvalidEmailAuthRecord(e));
+                None
+            }
+        }
+    }
+
+    /// Resolves TLSA, SSHFP or OPENPGPKEY records in presentation format from the DNS profile of
+    /// `qname`, falling back to the record's text key and, for SSHFP, to fingerprints of `dns.sshkey`.
+    async fn get_key_answer_async(&self, record: KeyRecord, qname: &DnsName, name: &DnsName) -> Option<String> {
+        let name = name.punycode_decode()?;
+        match self.get_dns_profile_records(qname, &name, record.rtype()).await {
+            Ok(records) => {
+                let limit = if matches!(record, KeyRecord::OpenPgpKey { .. }) { 1 } else { records.len() };
+                let formatted: Vec<String> = records
+                    .iter()
+                    .take(limit)
+                    .filter_map(|x| dns::keys::format_rdata(record.rtype(), &x.rdata))
+                    .collect();
+                if !formatted.is_empty() {
+                    return Some(formatted.join("\n"));
+                }
+            }
+            Err(e) => println!("error resolving dns profile of {:?} {:?}", qname, e),
+        }
+
+        let mut keys = vec![record.text_key()];
+        if record == KeyRecord::Sshfp {
+            keys.push(keys::SSH_PUBLIC_KEYS_TEXT_KEY.to_string());
+        }
+        for key in keys {
+            match self.get_text(&name, &key).await {
+                Ok(value) if value.is_empty() => continue,
+                Ok(value) if key == keys::SSH_PUBLIC_KEYS_TEXT_KEY => {
+                    let fingerprints = keys::sshfp_of_public_keys(&value);
+                    if fingerprints.is_none() {
+                        println!("error computing sshfp of {:?} {:?}", name, value);
+                    }
+                    return fingerprints;
+                }
+                Ok(value) => return Some(value),
+                Err(e) => {
+                    println!("error resolving {:?} of {:?} {:?}", key, name, e);
+                    return None;
+                }
+            }
+        }
+        None
+    }
+
+    /// Resolves CAA records in presentation format from the DNS profile of `qname`, falling back to
+    /// the `dns.caa` text key.
+    async fn get_caa_answer_async(&self, qname: &DnsName) -> Option<String> {
+        let name = qname.punycode_decode()?;
+        match self.get_dns_profile_records(qname, &name, dns::caa::CAA).await {
+            Ok(records) => {
+                let formatted: Vec<String> = records.iter().filter_map(|x| dns::caa::format_rdata(&x.rdata)).collect();
+                if !formatted.is_empty() {
+                    return Some(formatted.join("\n"));
+                }
+            }
+            Err(e) => println!("error resolving dns profile of {:?} {:?}", qname, e),
+        }
+        match self.get_text(&name, CAA_TEXT_KEY).await {
+            Ok(value) if !value.is_empty() => Some(value),
+            Ok(_) => None,
+            Err(e) => {
+                println!("error resolving {:?} of {:?} {:?}", CAA_TEXT_KEY, name, e);
+                None
+            }
+        }
+    }
+
+    /// Resolves the DID of an atproto (Bluesky) handle, malformed DIDs are not served.
+    async fn get_atproto_answer_async(&self, name: &DnsName) -> Option<String> {
+        let name = name.punycode_decode()?;
+        let res = self
+            .get_text(&name, atproto::TEXT_KEY)
+            .await
+            .and_then(|value| if value.is_empty() { Ok(None) } else { atproto::format_did(&value).map(Some) });
+        match res {
+            Ok(did) => did,
+            Err(e) => {
+                println!("error resolving atproto did of {:?} {:?}", name, e);
+                None
+            }
+        }
+    }
+
+    async fn get_avatar_answer_async(&self, name: &DnsName) -> Option<String> {
+        let name = name.punycode_decode()?;
+        match self.resolve_avatar_uri(&name).await {
+            Ok(uri) => uri,
+            Err(e) => {
+                println!("error resolving avatar of {:?} {:?}", name, e);
+                None
+            }
+        }
+    }
+
+    async fn resolve_question_async(&self, question: dns::DnsQuestion) -> Option<String> {
+        if question.qtype == 12 {
+            return self.get_reverse_answer_async(&question.qname).await;
+        }
+        if let Some((record, name)) = KeyRecord::parse_query(&question.qname, question.qtype) {
+            return self.get_key_answer_async(record, &question.qname, &name).await;
+        }
+        if question.qtype == dns::caa::CAA {
+            return self.get_caa_answer_async(&question.qname).await;
+        }
+        if question.qtype == 16 {
+            if let Some((record, name)) = ProfileRecord::parse_query(&question.qname) {
+                return self.get_profile_answer_async(record, &name).await;
+            }
+            if let Some((coin_type, name)) = coins::parse_coin_query(&question.qname) {
+                return self.get_coin_answer_async(coin_type, &name).await;
+            }
+            if let Some(name) = avatar::parse_query(&question.qname) {
+                return self.get_avatar_answer_async(&name).await;
+            }
+            if let Some(name) = atproto::parse_query(&question.qname) {
+                return self.get_atproto_answer_async(&name).await;
+            }
+            if let Some((record, name)) = EmailAuthRecord::parse_query(&question.qname) {
+                return self.get_email_auth_answer_async(record, &question.qname, &name).await;
+            }
+            if self.text_records.parse_query(&question.qname).is_none() {
+                return self.get_email_auth_answer_async(EmailAuthRecord::Spf, &question.qname, &question.qname).await;
+            }
+        }
+
+        let svc: Option<(String, DnsName)> = match question.qtype {
+            1 => Some(("A".to_string(), question.qname.clone())),
+            28 => Some(("AAAA".to_string(), question.qname.clone())),
+            _ => self.text_records.parse_query(&question.qname),
+        };
+
+        let res = match svc {
+            Some((key, name)) => {
+                self.get_text(&name.punycode_decode()?, &key).await
+            }
+            None => Err(DnsError::ErrNoServiceTypeRecognized)
+        };
+        match res {
+            Ok(r) => if !r.is_empty() {
+                Some(r)
+            } else {
+                None
+            },
+            Err(e) => {
+                println!("error resolving {:?} {:?}", question.qname, e);
+                None
+            }
+        }
+    }
+
+    /// Applies the registry and resolver logs since the last poll to the cache. Reorgs of the
+    /// block logs were applied up to and long gaps start the cache over, answers resolved at
+    /// other reorged blocks are dropped.
+    async fn poll_logs(&self, cache: &AnswerCache) -> Result<(), DnsError> {
+        let block = self.pin_block().await?;
+        cache.observe(block.number);
+        let Some(synced) = cache.synced() else {
+            cache.reset(block);
+            return Ok(());
+        };
+        let canonical = self.provider.get_block(synced.number).await?.and_then(|x| x.hash);
+        if canonical != Some(synced.hash) {
+            println!("block {} {:?} reorged, dropping cached answers", synced.number, synced.hash);
+            cache.reset(block);
+            return Ok(());
+        }
+        if block.number <= synced.number {
+            return Ok(());
+        }
+        if block.number - synced.number > MAX_LOG_RANGE.into() {
+            cache.reset(block);
+            return Ok(());
+        }
+        let filter = Filter::new().from_block(synced.number + 1).to_block(block.number).topic0(cache::topics());
+        let logs = self.provider.get_logs(&filter).await?;
+        // answers resolved ahead of the synced block are only kept when their block is canonical
+        let mut canonical = vec![];
+        for (number, hash) in cache.unconfirmed_blocks(block.number) {
+            if self.provider.get_block(number).await?.and_then(|x| x.hash) == Some(hash) {
+                canonical.push((number, hash));
+            }
+        }
+        cache.apply(logs.iter().filter_map(|x| cache::change_of(x, self.registry)), block, &canonical);
+        Ok(())
+    }
+
+    /// Polls logs for the cache until the server stops.
+    pub async fn watch_logs(self: Arc<Self>) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        loop {
+            if let Err(e) = self.poll_logs(cache).await {
+                println!("error polling resolver logs {:?}", e);
+            }
+            tokio::time::sleep(LOG_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn check_name_exists_async(&self, qname: &DnsName) -> bool {
+        let Some(name) = ens_name_of(qname, &self.text_records).punycode_decode() else {
+            return false;
+        };
+        if self.verify_proofs {
+            let slot = proof::registry_owner_slot(ens::namehash(&name));
+            return match self.get_verified_storage(self.registry, &[slot]).await {
+                // names only in the old registry cannot be told apart from missing names
+                Ok(owner) => !owner[0].is_zero() || self.registry_fallback,
+                Err(e) => {
+                    println!("error checking existence of {:?} {:?}", qname, e);
+                    true
+                }
+            };
+        }
+        let tx = TransactionRequest::new()
+            .to(self.registry)
+            .data([&ethers::utils::id("recordExists(bytes32)")[..], ens::namehash(&name).as_bytes()].concat());
+        match self.provider.call(&tx.into(), block::current()).await {
+            Ok(data) if data.is_empty() || data.iter().any(|x| *x != 0) => true,
+            // `recordExists` only looks at the registry itself, `owner` falls back to the old one
+            Ok(_) if self.registry_fallback && self.has_owner(&name).await => true,
+            // names without a registry record still exist below a wildcard resolver
+            Ok(_) => match self.find_resolver(&name).await {
+                Ok((resolver, false)) => self.supports_interface(resolver, EXTENDED_RESOLVER_INTERFACE).await,
+                _ => false,
+            },
+            Err(e) => {
+                println!("error checking existence of {:?} {:?}", qname, e);
+                true
+            }
+        }
+    }
+
+}
+
+#[async_trait]
+impl<T: Send + Sync + JsonRpcClient> dns::DnsAnswerProvider for EthersAnswerProvider<T> {
+    /// Resolves the question with every call pinned to one block, which is logged with the answer.
+    async fn get_answer_async(&self, question: dns::DnsQuestion) -> Option<String> {
+        if let Some(answer) = self.cache.as_ref().and_then(|x| x.get(&question)) {
+            return answer;
+        }
+        let block = match self.pin_block().await {
+            Ok(block) => block,
+            Err(e) => {
+                println!("error pinning block for {:?} {:?}", question.qname, e);
+                return None;
+            }
+        };
+        let (answer, reads) = cache::track(block.scope(self.resolve_question_async(question.clone()))).await;
+        if answer.is_some() {
+            println!("resolved {:?} type {} at block {} {:?}", question.qname, question.qtype, block.number, block.hash);
+        }
+        if let Some(cache) = &self.cache {
+            cache.insert(&question, &block, &answer, reads);
+        }
+        answer
+    }
+
+    async fn name_exists_async(&self, qname: &DnsName) -> bool {
+        match self.pin_block().await {
+            Ok(block) => block.scope(self.check_name_exists_async(qname)).await,
+            Err(e) => {
+                println!("error pinning block for {:?} {:?}", qname, e);
+                true
+            }
+        }
+    }
+
+    async fn get_zone_serial_async(&self) -> Option<u32> {
+        self.pin_block().await.ok().map(|x| x.number.low_u32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{JsonRpcError, MockProvider, MockResponse};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn answer_provider(provider: Provider<MockProvider>, ccip: CcipConfig) -> EthersAnswerProvider<MockProvider> {
+        EthersAnswerProvider {
+            provider,
+            text_records: TextRecordServices::default(),
+            avatar: AvatarConfig::default(),
+            ccip,
+            backend: ResolverBackend::Registry,
+            registry: ens::ENS_ADDRESS,
+            registry_fallback: false,
+            block: BlockConfig::default(),
+            verify_proofs: false,
+            public_resolvers: vec![],
+            light_client: None,
+            cache: None,
+        }
+    }
+
+    fn push_bytes(mock: &MockProvider, data: Vec<u8>) {
+        mock.push::<Bytes, _>(Bytes::from(data)).unwrap();
+    }
+
+    fn offchain_lookup_error(sender: Address, url: &str) -> MockResponse {
+        let lookup = abi::encode(&[
+            Token::Address(sender),
+            Token::Array(vec![Token::String(url.to_string())]),
+            Token::Bytes(vec![0xca, 0x11]),
+            Token::FixedBytes(ethers::utils::id("resolveWithProof(bytes,bytes)").to_vec()),
+            Token::Bytes(vec![0xe7]),
+        ]);
+        let revert = [&ethers::utils::id("OffchainLookup(address,string[],bytes,bytes4,bytes)")[..], &lookup].concat();
+        MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some(serde_json::Value::String(format!("0x{}", ethers::utils::hex::encode(revert)))),
+        })
+    }
+
+    /// Allows the stand-in gateways, loopback gateways are refused by default.
+    fn local_gateways() -> CcipConfig {
+        CcipConfig { allowlist: vec!["127.0.0.1".to_string()], ..Default::default() }
+    }
+
+    /// Serves a single request with `status` and `body`, returning the request line.
+    async fn stand_in_gateway(status: &'static str, body: String) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/{{sender}}/{{data}}.json", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|x| x == b"\r\n\r\n") {
+                let size = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..size]);
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_ccip_read_through_gateway() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let callback_result = abi::encode(&[Token::String("hello".to_string())]);
+        let body = serde_json::json!({ "data": "0xbeef" }).to_string();
+        let (url, gateway) = stand_in_gateway("200 OK", body).await;
+
+        let (provider, mock) = Provider::mocked();
+        // responses are popped from the back
+        push_bytes(&mock, callback_result);
+        mock.push_response(offchain_lookup_error(resolver, &url));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+
+        let text = answer_provider(provider, local_gateways()).get_text("vitalik.eth", "url").await.unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(gateway.await.unwrap(), format!("GET /{:?}/0xca11.json HTTP/1.1", resolver));
+    }
+
+    fn push_address(mock: &MockProvider, address: Address) {
+        push_bytes(mock, abi::encode(&[Token::Address(address)]));
+    }
+
+    #[test]
+    fn test_dns_encode() {
+        assert_eq!(dns_encode("sub.wild.eth").unwrap(), b"\x03sub\x04wild\x03eth\x00".to_vec());
+        assert_eq!(dns_encode(&"a".repeat(256)), None);
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_resolution_through_parent() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let (provider, mock) = Provider::mocked();
+        // responses are popped from the back
+        push_bytes(&mock, abi::encode(&[Token::Bytes(abi::encode(&[Token::String("hi".to_string())]))]));
+        push_bytes(&mock, abi::encode(&[Token::Bool(true)]));
+        push_address(&mock, resolver);
+        push_address(&mock, Address::zero());
+
+        let text = answer_provider(provider, CcipConfig::default()).get_text("sub.wild.eth", "url").await.unwrap();
+        assert_eq!(text, "hi");
+
+        // a parent resolver without resolve(bytes,bytes) does not resolve its subnames
+        let (provider, mock) = Provider::mocked();
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+        push_address(&mock, Address::zero());
+        let res = answer_provider(provider, CcipConfig::default()).get_text("sub.wild.eth", "url").await;
+        assert!(matches!(res, Err(DnsError::EnsResolverNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_universal_resolver_backend() {
+        let universal_resolver: Address = "0x0000000000000000000000000000000000000def".parse().unwrap();
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let (provider, mock) = Provider::mocked();
+        // one eth_call, no registry or supportsInterface round trips
+        let result = abi::encode(&[Token::String("hi".to_string())]);
+        push_bytes(&mock, abi::encode(&[Token::Bytes(result), Token::Address(resolver)]));
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.backend = ResolverBackend::UniversalResolver(universal_resolver);
+        assert_eq!(answer_provider.get_text("sub.wild.eth", "url").await.unwrap(), "hi");
+
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some(serde_json::Value::String(format!(
+                "0x{}",
+                ethers::utils::hex::encode(ethers::utils::id("ResolverNotFound()"))
+            ))),
+        }));
+        answer_provider.provider = provider;
+        let res = answer_provider.get_text("nobody.eth", "url").await;
+        assert!(matches!(res, Err(DnsError::EnsResolverNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_question_pinned_to_block() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let finalized = Block::<TxHash> { number: Some(0x10.into()), hash: Some(H256::repeat_byte(0xaa)), ..Default::default() };
+        let confirmed = Block::<TxHash> { number: Some(0x0e.into()), hash: Some(H256::repeat_byte(0xbb)), ..Default::default() };
+        let (provider, mock) = Provider::mocked();
+        // responses are popped from the back
+        push_bytes(&mock, abi::encode(&[Token::String("/ip4/192.0.2.1".to_string())]));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+        mock.push(confirmed).unwrap();
+        mock.push(finalized).unwrap();
+
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.block = BlockConfig { tag: BlockNumber::Finalized, confirmations: 2 };
+        let question = dns::DnsQuestion { qname: DnsName::from("vitalik.eth".to_string()), qtype: 1, qclass: 1 };
+        let answer = dns::DnsAnswerProvider::get_answer_async(&answer_provider, question).await;
+        assert_eq!(answer.unwrap(), "/ip4/192.0.2.1");
+
+        mock.assert_request("eth_getBlockByNumber", ("finalized", false)).unwrap();
+        mock.assert_request("eth_getBlockByNumber", ("0xe", false)).unwrap();
+        let tx: ethers::types::transaction::eip2718::TypedTransaction = ens::get_resolver(ens::ENS_ADDRESS, "vitalik.eth").into();
+        mock.assert_request("eth_call", (tx, BlockId::Hash(H256::repeat_byte(0xbb)))).unwrap();
+        // resolver calls are pinned too
+        let data = [&ethers::utils::id("supportsInterface(bytes4)")[..], &abi::encode(&[Token::FixedBytes(EXTENDED_RESOLVER_INTERFACE.to_vec())])].concat();
+        let tx: ethers::types::transaction::eip2718::TypedTransaction = TransactionRequest::new().to(resolver).data(data).into();
+        mock.assert_request("eth_call", (tx, BlockId::Hash(H256::repeat_byte(0xbb)))).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cache_invalidated_by_logs() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let block = |number: u64, hash: u8| Block::<TxHash> { number: Some(number.into()), hash: Some(H256::repeat_byte(hash)), ..Default::default() };
+        let push_answer = |mock: &MockProvider, answer: &str| {
+            // responses are popped from the back
+            push_bytes(mock, abi::encode(&[Token::String(answer.to_string())]));
+            push_bytes(mock, abi::encode(&[Token::Bool(false)]));
+            push_address(mock, resolver);
+            mock.push(block(0x10, 0xaa)).unwrap();
+        };
+        let (provider, mock) = Provider::mocked();
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.cache = Some(AnswerCache::default());
+        let cache = answer_provider.cache.as_ref().unwrap();
+        let question = dns::DnsQuestion { qname: DnsName::from("vitalik.eth".to_string()), qtype: 1, qclass: 1 };
+        let get_answer = || dns::DnsAnswerProvider::get_answer_async(&answer_provider, question.clone());
+
+        mock.push(block(0x10, 0xaa)).unwrap();
+        answer_provider.poll_logs(cache).await.unwrap();
+        push_answer(&mock, "/ip4/192.0.2.1");
+        assert_eq!(get_answer().await.unwrap(), "/ip4/192.0.2.1");
+        // answered without a request
+        assert_eq!(get_answer().await.unwrap(), "/ip4/192.0.2.1");
+
+        // other records of the name keep the answer
+        let text_changed = |key: &str| Log {
+            address: resolver,
+            topics: vec![H256::from(ethers::utils::keccak256("TextChanged(bytes32,string,string)")), ens::namehash("vitalik.eth")],
+            data: abi::encode(&[Token::String(key.to_string())]).into(),
+            ..Default::default()
+        };
+        mock.push::<Vec<Log>, _>(vec![text_changed("url")]).unwrap();
+        mock.push(block(0x10, 0xaa)).unwrap();
+        mock.push(block(0x11, 0xbb)).unwrap();
+        answer_provider.poll_logs(cache).await.unwrap();
+        assert_eq!(get_answer().await.unwrap(), "/ip4/192.0.2.1");
+
+        mock.push::<Vec<Log>, _>(vec![text_changed("A")]).unwrap();
+        mock.push(block(0x11, 0xbb)).unwrap();
+        mock.push(block(0x12, 0xcc)).unwrap();
+        answer_provider.poll_logs(cache).await.unwrap();
+        push_answer(&mock, "/ip4/192.0.2.2");
+        assert_eq!(get_answer().await.unwrap(), "/ip4/192.0.2.2");
+
+        // answers of extended resolvers are not cached
+        let other = dns::DnsQuestion { qname: DnsName::from("nick.eth".to_string()), qtype: 1, qclass: 1 };
+        push_bytes(&mock, abi::encode(&[Token::Bytes(abi::encode(&[Token::String("/ip4/192.0.2.3".to_string())]))]));
+        push_bytes(&mock, abi::encode(&[Token::Bool(true)]));
+        push_address(&mock, resolver);
+        mock.push(block(0x12, 0xcc)).unwrap();
+        assert_eq!(dns::DnsAnswerProvider::get_answer_async(&answer_provider, other.clone()).await.unwrap(), "/ip4/192.0.2.3");
+        assert_eq!(cache.get(&other), None);
+    }
+
+    #[tokio::test]
+    async fn test_light_client_pins_block() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let (provider, mock) = Provider::mocked();
+        // responses are popped from the back
+        push_bytes(&mock, abi::encode(&[Token::String("/ip4/192.0.2.1".to_string())]));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+
+        let light_client = crate::light_client::fixtures::light_client(Duration::from_secs(3600));
+        let finalized = light_client.finalized_execution();
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.light_client = Some(Arc::new(light_client));
+        assert_eq!(dns::DnsAnswerProvider::get_zone_serial_async(&answer_provider).await, Some(1128));
+        let question = dns::DnsQuestion { qname: DnsName::from("vitalik.eth".to_string()), qtype: 1, qclass: 1 };
+        let answer = dns::DnsAnswerProvider::get_answer_async(&answer_provider, question).await;
+        assert_eq!(answer.unwrap(), "/ip4/192.0.2.1");
+
+        // no block is asked from the RPC endpoint
+        let tx: ethers::types::transaction::eip2718::TypedTransaction = ens::get_resolver(ens::ENS_ADDRESS, "vitalik.eth").into();
+        mock.assert_request("eth_call", (tx, BlockId::Hash(finalized.block_hash))).unwrap();
+
+        // a finalized block older than the maximum age is not resolved at
+        answer_provider.light_client = Some(Arc::new(crate::light_client::fixtures::light_client(Duration::from_secs(600))));
+        assert!(matches!(answer_provider.pin_block().await, Err(DnsError::StaleFinalizedBlock(128))));
+    }
+
+    /// Pushes the proofs of the registry's owner and resolver, the resolver's (absent) record
+    /// version and the text record `url` = `hi` of vitalik.eth, tampering with the text record when
+    /// asked.
+    fn push_text_record_proofs(mock: &MockProvider, resolver: Address, tamper: bool) -> H256 {
+        let node = ens::namehash("vitalik.eth");
+        let text_slot = proof::resolver_field_slot(10, 0, &[node.as_bytes().to_vec(), b"url".to_vec()]);
+        let mut text = [0u8; 32];
+        text[..2].copy_from_slice(b"hi");
+        text[31] = 4;
+        let (state_root, proofs) = proof::fixtures::state(&[
+            (
+                ens::ENS_ADDRESS,
+                vec![
+                    (proof::registry_owner_slot(node), 0xd8da.into()),
+                    (proof::registry_resolver_slot(node), U256::from_big_endian(resolver.as_bytes())),
+                ],
+            ),
+            (resolver, vec![(text_slot, U256::from_big_endian(&text))]),
+        ]);
+        let mut version = proofs[1].clone();
+        version.storage_proof[0].key = proof::record_version_slot(node);
+        let mut text = proofs[1].clone();
+        if tamper {
+            for node in text.storage_proof[0].proof.iter_mut() {
+                let tampered: Vec<u8> = node.windows(2).position(|x| x == b"hi").map_or(node.to_vec(), |i| {
+                    let mut x = node.to_vec();
+                    x[i + 1] = b'o';
+                    x
+                });
+                *node = tampered.into();
+            }
+        }
+        // responses are popped from the back
+        mock.push(text).unwrap();
+        mock.push(version).unwrap();
+        mock.push(proofs[0].clone()).unwrap();
+        state_root
+    }
+
+    #[tokio::test]
+    async fn test_verified_text_record() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let (provider, mock) = Provider::mocked();
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.verify_proofs = true;
+        answer_provider.public_resolvers = vec![resolver];
+
+        let state_root = push_text_record_proofs(&mock, resolver, false);
+        let block = PinnedBlock { number: 1.into(), hash: H256::repeat_byte(0xbb), state_root };
+        assert_eq!(block.scope(answer_provider.get_text("vitalik.eth", "url")).await.unwrap(), "hi");
+
+        let state_root = push_text_record_proofs(&mock, resolver, true);
+        let block = PinnedBlock { state_root, ..block };
+        let res = block.scope(answer_provider.get_text("vitalik.eth", "url")).await;
+        assert!(matches!(res, Err(DnsError::InvalidProof(_))));
+
+        // without a pinned block, or for calls that storage proofs cannot cover, nothing is served
+        assert!(matches!(answer_provider.get_text("vitalik.eth", "url").await, Err(DnsError::InvalidProof(_))));
+        let res = block.scope(answer_provider.call_resolver("vitalik.eth", "pubkey(bytes32)", vec![])).await;
+        assert!(matches!(res, Err(DnsError::UnverifiableRecord(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verified_resolver_code() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let deployment = Address::repeat_byte(0xde);
+        let (provider, mock) = Provider::mocked();
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.verify_proofs = true;
+        answer_provider.public_resolvers = vec![deployment];
+
+        let node = ens::namehash("vitalik.eth");
+        let text_slot = proof::resolver_field_slot(10, 0, &[node.as_bytes().to_vec(), b"url".to_vec()]);
+        let mut text = [0u8; 32];
+        text[..2].copy_from_slice(b"hi");
+        text[31] = 4;
+        for (code, answered) in [(H256::repeat_byte(1), true), (H256::repeat_byte(2), false)] {
+            let (state_root, proofs) = proof::fixtures::state_with_code(&[
+                (
+                    ens::ENS_ADDRESS,
+                    H256::repeat_byte(3),
+                    vec![
+                        (proof::registry_owner_slot(node), 0xd8da.into()),
+                        (proof::registry_resolver_slot(node), U256::from_big_endian(resolver.as_bytes())),
+                    ],
+                ),
+                (resolver, code, vec![(text_slot, U256::from_big_endian(&text))]),
+                (deployment, H256::repeat_byte(1), vec![]),
+            ]);
+            let mut version = proofs[1].clone();
+            version.storage_proof[0].key = proof::record_version_slot(node);
+            // responses are popped from the back
+            if answered {
+                mock.push(proofs[1].clone()).unwrap();
+            }
+            mock.push(proofs[2].clone()).unwrap();
+            mock.push(version).unwrap();
+            mock.push(proofs[0].clone()).unwrap();
+
+            // a resolver with the storage of a PublicResolver is only read when it runs its code
+            let block = PinnedBlock { number: 1.into(), hash: H256::repeat_byte(0xbb), state_root };
+            let res = block.scope(answer_provider.get_text("vitalik.eth", "url")).await;
+            match answered {
+                true => assert_eq!(res.unwrap(), "hi"),
+                false => assert!(matches!(res, Err(DnsError::UnverifiableRecord(_)))),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verified_name_not_in_registry() {
+        let (provider, mock) = Provider::mocked();
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.verify_proofs = true;
+        answer_provider.registry_fallback = true;
+
+        // the registry only has a record of another name
+        let node = ens::namehash("vitalik.eth");
+        let (state_root, proofs) = proof::fixtures::state(&[(ens::ENS_ADDRESS, vec![(proof::registry_owner_slot(H256::repeat_byte(1)), 1.into())])]);
+        let mut registry = proofs[0].clone();
+        let absent = registry.storage_proof[0].clone();
+        registry.storage_proof = [proof::registry_owner_slot(node), proof::registry_resolver_slot(node)]
+            .into_iter()
+            .map(|key| ethers::types::StorageProof { key, value: 0.into(), ..absent.clone() })
+            .collect();
+        let block = PinnedBlock { number: 1.into(), hash: H256::repeat_byte(0xbb), state_root };
+
+        // a name that may only be in the old registry is not answered as missing
+        mock.push(registry.clone()).unwrap();
+        let res = block.scope(answer_provider.get_text("vitalik.eth", "url")).await;
+        assert!(matches!(res, Err(DnsError::UnverifiableRecord(_))));
+        mock.push(registry.clone()).unwrap();
+        assert!(block.scope(answer_provider.check_name_exists_async(&DnsName::from("vitalik.eth".to_string()))).await);
+
+        answer_provider.registry_fallback = false;
+        mock.push(registry.clone()).unwrap();
+        let res = block.scope(answer_provider.get_text("vitalik.eth", "url")).await;
+        assert!(matches!(res, Err(DnsError::EnsResolverNotFound(_))));
+        mock.push(registry).unwrap();
+        assert!(!block.scope(answer_provider.check_name_exists_async(&DnsName::from("vitalik.eth".to_string()))).await);
+    }
+
+    #[tokio::test]
+    async fn test_name_only_in_old_registry() {
+        let (provider, mock) = Provider::mocked();
+        let mut answer_provider = answer_provider(provider, CcipConfig::default());
+        answer_provider.registry_fallback = true;
+        let qname = DnsName::from("vitalik.eth".to_string());
+
+        // responses are popped from the back: recordExists, then owner from the old registry
+        push_address(&mock, Address::repeat_byte(1));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        assert!(answer_provider.check_name_exists_async(&qname).await);
+
+        // without an owner in either registry the name only exists below a wildcard resolver
+        push_address(&mock, Address::zero());
+        push_address(&mock, Address::zero());
+        push_address(&mock, Address::zero());
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        assert!(!answer_provider.check_name_exists_async(&qname).await);
+    }
+
+    #[tokio::test]
+    async fn test_ccip_read_limits() {
+        let resolver: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(offchain_lookup_error(resolver, "https://gateway.example/{data}"));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+        let config = CcipConfig { max_redirects: 0, ..Default::default() };
+        let res = answer_provider(provider, config).get_text("vitalik.eth", "url").await;
+        assert!(matches!(res, Err(DnsError::CcipReadError(_))));
+
+        let (url, gateway) = stand_in_gateway("404 Not Found", "{}".to_string()).await;
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(offchain_lookup_error(resolver, &url));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+        let res = answer_provider(provider, local_gateways()).get_text("vitalik.eth", "url").await;
+        assert!(matches!(res, Err(DnsError::CcipReadError(_))));
+        gateway.await.unwrap();
+
+        // a loopback gateway is refused by default
+        let (url, gateway) = stand_in_gateway("200 OK", serde_json::json!({ "data": "0xbeef" }).to_string()).await;
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(offchain_lookup_error(resolver, &url));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+        let res = answer_provider(provider, CcipConfig::default()).get_text("vitalik.eth", "url").await;
+        assert!(matches!(res, Err(DnsError::CcipReadError(_))));
+        assert!(!gateway.is_finished());
+        gateway.abort();
+
+        let (provider, mock) = Provider::mocked();
+        mock.push_response(offchain_lookup_error(resolver, "https://gateway.example/{data}"));
+        push_bytes(&mock, abi::encode(&[Token::Bool(false)]));
+        push_address(&mock, resolver);
+        let config = CcipConfig { allowlist: vec!["ccip.example".to_string()], ..Default::default() };
+        let res = answer_provider(provider, config).get_text("vitalik.eth", "url").await;
+        assert!(matches!(res, Err(DnsError::CcipReadError(_))));
+    }
+}
@@ -0,0 +1,121 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ethers::abi::{self, ParamType, Token};
+use ethers::types::U256;
+use ethers::utils::{hex, to_checksum};
+
+use super::contenthash;
+use crate::dns::DnsName;
+
+/// ABI content types requested from `ABI(bytes32,uint256)`: JSON, zlib JSON, CBOR and URI.
+const ABI_CONTENT_TYPES: u64 = 0b1111;
+
+/// Standard resolver profiles served as TXT under a reserved `_<label>.<name>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileRecord {
+    Eth,
+    Contenthash,
+    Pubkey,
+    Abi,
+}
+
+impl ProfileRecord {
+    pub fn parse_query(qname: &DnsName) -> Option<(ProfileRecord, DnsName)> {
+        let [first, rest @ ..] = qname.labels.as_slice() else {
+            return None;
+        };
+        let record = match first.label.to_ascii_lowercase().as_slice() {
+            b"_eth" => ProfileRecord::Eth,
+            b"_contenthash" => ProfileRecord::Contenthash,
+            b"_pubkey" => ProfileRecord::Pubkey,
+            b"_abi" => ProfileRecord::Abi,
+            _ => return None,
+        };
+        (!rest.is_empty()).then(|| (record, DnsName { labels: rest.to_vec() }))
+    }
+
+    pub fn signature(&self) -> &'static str {
+        match self {
+            ProfileRecord::Eth => "addr(bytes32)",
+            ProfileRecord::Contenthash => "contenthash(bytes32)",
+            ProfileRecord::Pubkey => "pubkey(bytes32)",
+            ProfileRecord::Abi => "ABI(bytes32,uint256)",
+        }
+    }
+
+    pub fn params(&self) -> Vec<Token> {
+        match self {
+            ProfileRecord::Abi => vec![Token::Uint(ABI_CONTENT_TYPES.into())],
+            _ => vec![],
+        }
+    }
+
+    /// Decodes the resolver's return data and renders it as TXT, `None` when the record is unset.
+    ///
+    /// * `_eth`: `0x<EIP-55 address>`
+    /// * `_contenthash`: `ipfs://<cid>`, `ipns://<cid>`, `bzz://<hash>`, `ar://<id>`, `onion://<host>` or `0x<hex>`
+    /// * `_pubkey`: `x=0x<hex> y=0x<hex>`
+    /// * `_abi`: `content-type=<n> data=<base64>`
+    pub fn format(&self, data: &[u8]) -> Option<String> {
+        match self {
+            ProfileRecord::Eth => {
+                let address = abi::decode(&[ParamType::Address], data).ok()?.pop()?.into_address()?;
+                (!address.is_zero()).then(|| to_checksum(&address, None))
+            }
+            ProfileRecord::Contenthash => {
+                let hash = abi::decode(&[ParamType::Bytes], data).ok()?.pop()?.into_bytes()?;
+                contenthash::to_uri(&hash)
+            }
+            ProfileRecord::Pubkey => {
+                let mut tokens = abi::decode(&[ParamType::FixedBytes(32), ParamType::FixedBytes(32)], data).ok()?;
+                let y = tokens.pop()?.into_fixed_bytes()?;
+                let x = tokens.pop()?.into_fixed_bytes()?;
+                if x.iter().chain(y.iter()).all(|b| *b == 0) {
+                    return None;
+                }
+                Some(format!("x=0x{} y=0x{}", hex::encode(x), hex::encode(y)))
+            }
+            ProfileRecord::Abi => {
+                let mut tokens = abi::decode(&[ParamType::Uint(256), ParamType::Bytes], data).ok()?;
+                let abi_data = tokens.pop()?.into_bytes()?;
+                let content_type = tokens.pop()?.into_uint()?;
+                if content_type == U256::zero() {
+                    return None;
+                }
+                Some(format!("content-type={} data={}", content_type, STANDARD.encode(abi_data)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            ProfileRecord::parse_query(&DnsName::from("_contenthash.vitalik.eth".to_string())),
+            Some((ProfileRecord::Contenthash, DnsName::from("vitalik.eth".to_string())))
+        );
+        assert_eq!(ProfileRecord::parse_query(&DnsName::from("_abi".to_string())), None);
+    }
+
+    #[test]
+    fn test_format() {
+        let address: Address = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".parse().unwrap();
+        let data = abi::encode(&[Token::Address(address)]);
+        assert_eq!(ProfileRecord::Eth.format(&data).unwrap(), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+        assert_eq!(ProfileRecord::Eth.format(&abi::encode(&[Token::Address(Address::zero())])), None);
+
+        let data = abi::encode(&[Token::Uint(1.into()), Token::Bytes(b"[]".to_vec())]);
+        assert_eq!(ProfileRecord::Abi.format(&data).unwrap(), "content-type=1 data=W10=");
+
+        let data = abi::encode(&[Token::FixedBytes(vec![0x11; 32]), Token::FixedBytes(vec![0x22; 32])]);
+        assert_eq!(
+            ProfileRecord::Pubkey.format(&data).unwrap(),
+            format!("x=0x{} y=0x{}", "11".repeat(32), "22".repeat(32))
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+
+use ethers::abi::Token;
+use ethers::types::{Address, Bytes, EIP1186ProofResponse, H256, U256};
+use ethers::utils::keccak256;
+use ethers::utils::rlp::Rlp;
+
+use crate::dns::DnsError;
+
+/// Root of an empty Merkle Patricia trie, `keccak256(rlp(""))`.
+const EMPTY_TRIE_ROOT: [u8; 32] = [
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e, 0x5b, 0x48, 0xe0, 0x1b, 0x99,
+    0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+];
+
+/// Code hash of accounts without code, `keccak256("")`.
+const EMPTY_CODE_HASH: [u8; 32] = [
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca,
+    0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+];
+
+/// PublicResolver deployments per chain, resolvers running their code share their storage layout.
+const PUBLIC_RESOLVERS: &[(u64, &str)] = &[
+    (1, "0x231b0Ee14048e9dCcD1d247744d114a4EB5E8E63"),
+    (1, "0xF29100983E058B709F3D539b0c765937B804AC15"),
+    (11155111, "0x8FADE66B79cC9f707aB26799354482EB93a5B7dD"),
+];
+
+/// Longest `bytes`/`string` value read from storage.
+const MAX_STORED_BYTES: usize = 8192;
+
+fn invalid(e: impl std::fmt::Display) -> DnsError {
+    DnsError::InvalidProof(e.to_string())
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|x| [x >> 4, x & 0x0f]).collect()
+}
+
+/// Decodes a hex-prefix encoded path into its nibbles and whether it ends in a leaf.
+fn decode_path(encoded: &[u8]) -> Result<(Vec<u8>, bool), DnsError> {
+    let (first, rest) = encoded.split_first().ok_or_else(|| invalid("empty node path"))?;
+    let flag = first >> 4;
+    let mut path = if flag & 1 == 1 { vec![first & 0x0f] } else { vec![] };
+    path.extend(nibbles(rest));
+    Ok((path, flag & 2 == 2))
+}
+
+/// Walks the Merkle Patricia trie with root `root` down to `keccak256(key)` using the nodes in
+/// `proof`, returning the value stored there or `None` when the proof shows it is absent.
+pub fn verify_proof(root: H256, key: &[u8], proof: &[Bytes]) -> Result<Option<Vec<u8>>, DnsError> {
+    if root.0 == EMPTY_TRIE_ROOT {
+        return Ok(None);
+    }
+    let nodes: HashMap<H256, &[u8]> = proof.iter().map(|x| (H256(keccak256(x)), x.as_ref())).collect();
+    let lookup = |hash: H256| nodes.get(&hash).map(|x| x.to_vec()).ok_or_else(|| invalid(format!("missing trie node {:?}", hash)));
+
+    let path = nibbles(&keccak256(key));
+    let mut path = path.as_slice();
+    let mut node = lookup(root)?;
+    loop {
+        let next = {
+            let rlp = Rlp::new(&node);
+            let child = match rlp.item_count()? {
+                17 => {
+                    let Some((nibble, rest)) = path.split_first() else {
+                        return Err(invalid("key ends at a branch node"));
+                    };
+                    path = rest;
+                    rlp.at(*nibble as usize)?
+                }
+                2 => {
+                    let (prefix, leaf) = decode_path(rlp.at(0)?.data()?)?;
+                    if leaf {
+                        if prefix != path {
+                            return Ok(None);
+                        }
+                        return Ok(Some(rlp.at(1)?.data()?.to_vec()));
+                    }
+                    match path.strip_prefix(prefix.as_slice()) {
+                        Some(rest) => path = rest,
+                        None => return Ok(None),
+                    }
+                    rlp.at(1)?
+                }
+                _ => return Err(invalid("malformed trie node")),
+            };
+            // nodes shorter than 32 bytes are embedded in their parent
+            if child.is_list() {
+                child.as_raw().to_vec()
+            } else {
+                match child.data()? {
+                    [] => return Ok(None),
+                    hash if hash.len() == 32 => lookup(H256::from_slice(hash))?,
+                    _ => return Err(invalid("malformed node reference")),
+                }
+            }
+        };
+        node = next;
+    }
+}
+
+/// Verifies the account proof against `state_root` and returns the account's storage root and
+/// code hash.
+fn verify_account(state_root: H256, proof: &EIP1186ProofResponse) -> Result<(H256, H256), DnsError> {
+    let Some(account) = verify_proof(state_root, proof.address.as_bytes(), &proof.account_proof)? else {
+        return Ok((H256(EMPTY_TRIE_ROOT), H256(EMPTY_CODE_HASH)));
+    };
+    // [nonce, balance, storageRoot, codeHash]
+    let rlp = Rlp::new(&account);
+    match (rlp.at(2)?.data()?, rlp.at(3)?.data()?) {
+        (root, code_hash) if rlp.item_count()? == 4 && root.len() == 32 && code_hash.len() == 32 => {
+            Ok((H256::from_slice(root), H256::from_slice(code_hash)))
+        }
+        _ => Err(invalid("malformed account")),
+    }
+}
+
+/// The code hash of the account proven by `proof` against `state_root`, `None` without code.
+pub fn verify_code_hash(state_root: H256, proof: &EIP1186ProofResponse) -> Result<Option<H256>, DnsError> {
+    let (_, code_hash) = verify_account(state_root, proof)?;
+    Ok((code_hash.0 != EMPTY_CODE_HASH).then_some(code_hash))
+}
+
+/// The values of `slots` proven by `proof` against `state_root`, ignoring the values the node claims.
+pub fn verify_storage(state_root: H256, proof: &EIP1186ProofResponse, slots: &[H256]) -> Result<Vec<U256>, DnsError> {
+    let (storage_root, _) = verify_account(state_root, proof)?;
+    slots
+        .iter()
+        .map(|slot| {
+            let storage = proof
+                .storage_proof
+                .iter()
+                .find(|x| x.key == *slot)
+                .ok_or_else(|| invalid(format!("missing storage proof of {:?}", slot)))?;
+            match verify_proof(storage_root, slot.as_bytes(), &storage.proof)? {
+                None => Ok(U256::zero()),
+                Some(value) => match Rlp::new(&value).data()? {
+                    data if data.len() <= 32 => Ok(U256::from_big_endian(data)),
+                    _ => Err(invalid("malformed storage value")),
+                },
+            }
+        })
+        .collect()
+}
+
+/// A 32 byte big endian storage word.
+pub fn word(value: U256) -> H256 {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    H256(word)
+}
+
+fn slot_number(slot: u64) -> H256 {
+    H256::from_low_u64_be(slot)
+}
+
+/// Storage slot of `mapping[key]` for a mapping at `slot`, value type keys are padded to 32 bytes.
+fn mapping_slot(slot: H256, key: &[u8]) -> H256 {
+    H256(keccak256([key, slot.as_bytes()].concat()))
+}
+
+/// The mapping key a call argument is stored under.
+pub fn mapping_key(token: &Token) -> Option<Vec<u8>> {
+    match token {
+        Token::FixedBytes(x) if x.len() == 32 => Some(x.clone()),
+        Token::Uint(x) => Some(word(*x).as_bytes().to_vec()),
+        Token::String(x) => Some(x.as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+/// `records[node].owner` of the ENS registry, `records` is at slot 0.
+pub fn registry_owner_slot(node: H256) -> H256 {
+    mapping_slot(slot_number(0), node.as_bytes())
+}
+
+/// `records[node].resolver`, the slot after the owner.
+pub fn registry_resolver_slot(node: H256) -> H256 {
+    word(U256::from_big_endian(registry_owner_slot(node).as_bytes()) + 1)
+}
+
+/// The known PublicResolver deployments on `chain_id`.
+pub fn public_resolvers(chain_id: u64) -> Vec<Address> {
+    PUBLIC_RESOLVERS
+        .iter()
+        .filter(|(x, _)| *x == chain_id)
+        .filter_map(|(_, address)| address.parse().ok())
+        .collect()
+}
+
+/// `recordVersions[node]` of the PublicResolver.
+pub fn record_version_slot(node: H256) -> H256 {
+    mapping_slot(slot_number(0), node.as_bytes())
+}
+
+/// How a PublicResolver field is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Address,
+    Bytes,
+    String,
+}
+
+impl FieldKind {
+    pub fn token(self, value: Vec<u8>) -> Token {
+        match self {
+            FieldKind::Address if value.len() == 20 => Token::Address(Address::from_slice(&value)),
+            FieldKind::Address => Token::Address(Address::zero()),
+            FieldKind::Bytes => Token::Bytes(value),
+            FieldKind::String => Token::String(String::from_utf8_lossy(&value).into_owned()),
+        }
+    }
+}
+
+/// Storage slot of the PublicResolver `versionable_*` mapping a resolver method reads.
+pub fn resolver_field(signature: &str) -> Option<(u64, FieldKind)> {
+    match signature {
+        "addr(bytes32)" => Some((2, FieldKind::Address)),
+        "addr(bytes32,uint256)" => Some((2, FieldKind::Bytes)),
+        "contenthash(bytes32)" => Some((3, FieldKind::Bytes)),
+        "dnsRecord(bytes32,bytes32,uint16)" => Some((5, FieldKind::Bytes)),
+        "name(bytes32)" => Some((8, FieldKind::String)),
+        "text(bytes32,string)" => Some((10, FieldKind::String)),
+        _ => None,
+    }
+}
+
+/// Slot of `versionable_<field>[version][keys[0]][keys[1]]...`.
+pub fn resolver_field_slot(field: u64, version: u64, keys: &[Vec<u8>]) -> H256 {
+    let version_slot = mapping_slot(slot_number(field), slot_number(version).as_bytes());
+    keys.iter().fold(version_slot, |slot, key| mapping_slot(slot, key))
+}
+
+/// A Solidity `bytes` or `string` stored at a slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredBytes {
+    /// Values shorter than 32 bytes live in the slot itself.
+    Short(Vec<u8>),
+    /// Longer values live in consecutive slots from `keccak256(slot)`.
+    Long { len: usize, slots: Vec<H256> },
+}
+
+/// Decodes the slot `slot` holding `value`.
+pub fn stored_bytes(slot: H256, value: U256) -> Result<StoredBytes, DnsError> {
+    // the resolver is chosen by the name's owner, its storage may hold anything
+    if !value.bit(0) {
+        let len = (value.low_u32() & 0xff) as usize / 2;
+        if len > 31 {
+            return Err(invalid(format!("short stored value of {} bytes", len)));
+        }
+        return Ok(StoredBytes::Short(word(value)[..len].to_vec()));
+    }
+    let len = usize::try_from(value / 2).map_err(|_| invalid(format!("stored value length {}", value / 2)))?;
+    if len > MAX_STORED_BYTES {
+        return Err(invalid(format!("stored value of {} bytes", len)));
+    }
+    let start = U256::from_big_endian(&keccak256(slot));
+    let slots = (0..len.div_ceil(32)).map(|i| word(start + i)).collect();
+    Ok(StoredBytes::Long { len, slots })
+}
+
+/// Joins the values of the slots of a long `StoredBytes`.
+pub fn join_stored_bytes(len: usize, values: &[U256]) -> Vec<u8> {
+    let mut data: Vec<u8> = values.iter().flat_map(|x| word(*x).0).collect();
+    data.truncate(len);
+    data
+}
+
+/// Builds tries and proofs the way a node's `eth_getProof` would return them.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use ethers::types::StorageProof;
+    use ethers::utils::rlp::{self, RlpStream};
+
+    fn encode_path(path: &[u8], leaf: bool) -> Vec<u8> {
+        let flag = if leaf { 2 } else { 0 };
+        let (mut encoded, rest) = match path.split_first() {
+            Some((first, rest)) if path.len() % 2 == 1 => (vec![((flag + 1) << 4) | first], rest),
+            _ => (vec![flag << 4], path),
+        };
+        encoded.extend(rest.chunks(2).map(|x| (x[0] << 4) | x[1]));
+        encoded
+    }
+
+    fn append_child(stream: &mut RlpStream, child: Vec<u8>, nodes: &mut Vec<Bytes>) {
+        if child.len() < 32 {
+            stream.append_raw(&child, 1);
+        } else {
+            stream.append(&keccak256(&child).as_slice());
+            nodes.push(child.into());
+        }
+    }
+
+    fn node(entries: &[(Vec<u8>, Vec<u8>)], nodes: &mut Vec<Bytes>) -> Vec<u8> {
+        if let [(path, value)] = entries {
+            let mut stream = RlpStream::new_list(2);
+            stream.append(&encode_path(path, true)).append(value);
+            return stream.out().to_vec();
+        }
+        let first = &entries[0].0;
+        let common = (0..first.len()).take_while(|i| entries.iter().all(|(x, _)| x[*i] == first[*i])).count();
+        if common > 0 {
+            let rest: Vec<_> = entries.iter().map(|(x, v)| (x[common..].to_vec(), v.clone())).collect();
+            let mut stream = RlpStream::new_list(2);
+            stream.append(&encode_path(&first[..common], false));
+            append_child(&mut stream, node(&rest, nodes), nodes);
+            return stream.out().to_vec();
+        }
+        let mut stream = RlpStream::new_list(17);
+        for nibble in 0..16 {
+            let children: Vec<_> = entries.iter().filter(|(x, _)| x[0] == nibble).map(|(x, v)| (x[1..].to_vec(), v.clone())).collect();
+            if children.is_empty() {
+                stream.append_empty_data();
+            } else {
+                append_child(&mut stream, node(&children, nodes), nodes);
+            }
+        }
+        stream.append_empty_data();
+        stream.out().to_vec()
+    }
+
+    /// Root and nodes of the trie holding `value` at `keccak256(key)` for each entry.
+    pub fn trie(entries: &[(Vec<u8>, Vec<u8>)]) -> (H256, Vec<Bytes>) {
+        if entries.is_empty() {
+            return (H256(EMPTY_TRIE_ROOT), vec![]);
+        }
+        let entries: Vec<_> = entries.iter().map(|(k, v)| (nibbles(&keccak256(k)), v.clone())).collect();
+        let mut nodes = vec![];
+        let root = node(&entries, &mut nodes);
+        nodes.push(root.clone().into());
+        (H256(keccak256(&root)), nodes)
+    }
+
+    pub fn account(storage_root: H256, code_hash: H256) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&0u64).append(&U256::zero()).append(&storage_root.as_bytes()).append(&code_hash.as_bytes());
+        stream.out().to_vec()
+    }
+
+    /// State root and `eth_getProof` responses for accounts with the given storage, each running
+    /// code of its own.
+    pub fn state(accounts: &[(Address, Vec<(H256, U256)>)]) -> (H256, Vec<EIP1186ProofResponse>) {
+        let accounts: Vec<_> = accounts.iter().map(|(address, slots)| (*address, H256(keccak256(address)), slots.clone())).collect();
+        state_with_code(&accounts)
+    }
+
+    /// An account's address, code hash and storage.
+    pub type Account = (Address, H256, Vec<(H256, U256)>);
+
+    /// `state` with the code hash of each account given.
+    pub fn state_with_code(accounts: &[Account]) -> (H256, Vec<EIP1186ProofResponse>) {
+        let storage: Vec<_> = accounts
+            .iter()
+            .map(|(_, _, slots)| {
+                let entries: Vec<_> = slots.iter().map(|(k, v)| (k.as_bytes().to_vec(), rlp::encode(v).to_vec())).collect();
+                trie(&entries)
+            })
+            .collect();
+        let entries: Vec<_> = accounts
+            .iter()
+            .zip(&storage)
+            .map(|((address, code_hash, _), (root, _))| (address.as_bytes().to_vec(), account(*root, *code_hash)))
+            .collect();
+        let (state_root, account_proof) = trie(&entries);
+        let responses = accounts
+            .iter()
+            .zip(storage)
+            .map(|((address, code_hash, slots), (storage_hash, proof))| EIP1186ProofResponse {
+                address: *address,
+                code_hash: *code_hash,
+                storage_hash,
+                account_proof: account_proof.clone(),
+                storage_proof: slots.iter().map(|(key, value)| StorageProof { key: *key, proof: proof.clone(), value: *value }).collect(),
+                ..Default::default()
+            })
+            .collect();
+        (state_root, responses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_proof() {
+        let entries: Vec<_> = (0u8..20).map(|i| (vec![i], vec![i; 40])).collect();
+        let (root, proof) = fixtures::trie(&entries);
+        for (key, value) in &entries {
+            assert_eq!(verify_proof(root, key, &proof).unwrap().as_ref(), Some(value));
+        }
+        assert_eq!(verify_proof(root, &[0xff], &proof).unwrap(), None);
+
+        // a tampered value no longer hashes to the node its parent references
+        let mut tampered = proof.clone();
+        let leaf = tampered.iter().position(|x| x.windows(40).any(|w| w == [7; 40])).unwrap();
+        let mut node = tampered[leaf].to_vec();
+        let len = node.len();
+        node[len - 1] = 8;
+        tampered[leaf] = node.into();
+        assert!(verify_proof(root, &[7], &tampered).is_err());
+        assert!(verify_proof(H256::repeat_byte(1), &[7], &proof).is_err());
+    }
+
+    #[test]
+    fn test_verify_storage() {
+        let contract: Address = "0x0000000000000000000000000000000000000abc".parse().unwrap();
+        let other: Address = "0x0000000000000000000000000000000000000def".parse().unwrap();
+        let slot = registry_resolver_slot(H256::repeat_byte(1));
+        let (state_root, proofs) = fixtures::state(&[(contract, vec![(slot, 42.into())]), (other, vec![(slot, 1.into())])]);
+        assert_eq!(verify_storage(state_root, &proofs[0], &[slot]).unwrap(), vec![U256::from(42)]);
+
+        let mut claimed = proofs[0].clone();
+        claimed.storage_hash = H256::repeat_byte(2);
+        claimed.storage_proof[0].value = 43.into();
+        assert_eq!(verify_storage(state_root, &claimed, &[slot]).unwrap(), vec![U256::from(42)]);
+        assert!(verify_storage(H256::repeat_byte(3), &proofs[0], &[slot]).is_err());
+        assert!(verify_storage(state_root, &proofs[0], &[H256::zero()]).is_err());
+
+        // the code hash is proven too, accounts without code have none
+        let mut claimed = proofs[0].clone();
+        claimed.code_hash = H256::repeat_byte(2);
+        assert_eq!(verify_code_hash(state_root, &claimed).unwrap(), Some(H256(keccak256(contract))));
+        let (state_root, proofs) = fixtures::state_with_code(&[(contract, H256(keccak256([])), vec![])]);
+        assert_eq!(verify_code_hash(state_root, &proofs[0]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_stored_bytes() {
+        let slot = H256::repeat_byte(1);
+        let mut short = [0u8; 32];
+        short[..2].copy_from_slice(b"hi");
+        short[31] = 4;
+        assert_eq!(stored_bytes(slot, U256::from_big_endian(&short)).unwrap(), StoredBytes::Short(b"hi".to_vec()));
+
+        let StoredBytes::Long { len, slots } = stored_bytes(slot, U256::from(40 * 2 + 1)).unwrap() else {
+            panic!("expected a long value");
+        };
+        assert_eq!((len, slots.len()), (40, 2));
+        assert_eq!(slots[0], H256(keccak256(slot)));
+        let values = [U256::from_big_endian(&[b'a'; 32]), U256::from_big_endian(&[b'b'; 32])];
+        assert_eq!(join_stored_bytes(len, &values), [[b'a'; 32].as_slice(), &[b'b'; 8]].concat());
+        assert!(stored_bytes(slot, U256::from(MAX_STORED_BYTES * 2 + 3)).is_err());
+
+        // malformed slots are invalid proofs, not panics
+        assert!(matches!(stored_bytes(slot, U256::from(0xfe)), Err(DnsError::InvalidProof(_))));
+        assert!(matches!(stored_bytes(slot, U256::MAX), Err(DnsError::InvalidProof(_))));
+    }
+}
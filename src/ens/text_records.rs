@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use crate::dns::DnsName;
+
+/// Label of the `_text.<key>.<name>` escape hatch, `<key>` is a single label so keys
+/// containing dots are queried with the dots escaped (`_text.com\.github.vitalik.eth`).
+const TEXT_ESCAPE_LABEL: &[u8] = b"_text";
+
+/// Text records served when no mapping is configured, each under a DNS label equal to its key.
+const DEFAULT_TEXT_RECORDS: &[&str] = &[
+    "avatar",
+    "description",
+    "display",
+    "email",
+    "keywords",
+    "mail",
+    "notice",
+    "location",
+    "phone",
+    "url",
+    "com.github",
+    "com.peepeth",
+    "com.linkedin",
+    "com.twitter",
+    "io.keybase",
+    "org.telegram",
+];
+
+/// Maps the DNS labels in front of an ENS name to the ENS text record they are served from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextRecordServices {
+    /// Sorted by descending label count so `com.github` wins over a shorter `github`.
+    services: Vec<(DnsName, String)>,
+}
+
+impl Default for TextRecordServices {
+    fn default() -> Self {
+        TextRecordServices::new(DEFAULT_TEXT_RECORDS.iter().map(|x| (x.to_string(), x.to_string())))
+    }
+}
+
+impl TextRecordServices {
+    /// Builds the mapping from `(dns labels, ens text key)` pairs.
+    pub fn new(services: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut services: Vec<(DnsName, String)> = services
+            .into_iter()
+            .map(|(labels, key)| (DnsName::from(labels.trim_end_matches('.').to_string()), key))
+            .collect();
+        services.sort_by_key(|(labels, _)| std::cmp::Reverse(labels.labels.len()));
+        TextRecordServices { services }
+    }
+
+    /// Parses a JSON object of DNS labels to ENS text keys, e.g. `{"_nostr": "org.nostr"}`.
+    /// The configured mapping replaces the built-in defaults.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let services: BTreeMap<String, String> = serde_json::from_str(json)?;
+        Ok(TextRecordServices::new(services))
+    }
+
+    /// Splits a query into the ENS text key and the ENS name it is read from.
+    pub fn parse_query(&self, qname: &DnsName) -> Option<(String, DnsName)> {
+        if let [first, key, rest @ ..] = qname.labels.as_slice() {
+            if first.label.eq_ignore_ascii_case(TEXT_ESCAPE_LABEL) {
+                let key = String::from_utf8(key.label.clone()).ok()?;
+                return (!rest.is_empty()).then(|| (key, DnsName { labels: rest.to_vec() }));
+            }
+        }
+
+        self.services
+            .iter()
+            .filter(|(labels, _)| labels.labels.len() < qname.labels.len())
+            .find(|(labels, _)| labels.is_label_of(qname))
+            .and_then(|(labels, key)| Some((key.clone(), qname.remove_prefix_labels(labels)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::DnsLabel;
+
+    #[test]
+    fn test_default_services() {
+        let services = TextRecordServices::default();
+        assert_eq!(
+            services.parse_query(&DnsName::from("com.github.vitalik.eth".to_string())),
+            Some(("com.github".to_string(), DnsName::from("vitalik.eth".to_string())))
+        );
+        assert_eq!(services.parse_query(&DnsName::from("vitalik.eth".to_string())), None);
+        assert_eq!(services.parse_query(&DnsName::from("avatar".to_string())), None);
+    }
+
+    #[test]
+    fn test_configured_renames() {
+        let services = TextRecordServices::from_json(r#"{"_nostr": "org.nostr", "github": "github", "x.github": "com.github"}"#).unwrap();
+        assert_eq!(
+            services.parse_query(&DnsName::from("_nostr.vitalik.eth".to_string())),
+            Some(("org.nostr".to_string(), DnsName::from("vitalik.eth".to_string())))
+        );
+        assert_eq!(
+            services.parse_query(&DnsName::from("x.github.vitalik.eth".to_string())),
+            Some(("com.github".to_string(), DnsName::from("vitalik.eth".to_string())))
+        );
+        assert_eq!(services.parse_query(&DnsName::from("avatar.vitalik.eth".to_string())), None);
+        assert!(TextRecordServices::from_json("[]").is_err());
+    }
+
+    #[test]
+    fn test_text_escape() {
+        let services = TextRecordServices::default();
+        let mut qname = DnsName::from("vitalik.eth".to_string());
+        qname.labels.insert(0, DnsLabel::from("com.discord".to_string()));
+        qname.labels.insert(0, DnsLabel::from("_text".to_string()));
+        assert_eq!(
+            services.parse_query(&qname),
+            Some(("com.discord".to_string(), DnsName::from("vitalik.eth".to_string())))
+        );
+        assert_eq!(services.parse_query(&DnsName::from("_text.eth".to_string())), None);
+    }
+}
@@ -0,0 +1,63 @@
+use ethers::types::Address;
+
+use super::ccip;
+use crate::dns::DnsError;
+
+/// UniversalResolver deployments of ENS, by chain id.
+pub const UNIVERSAL_RESOLVERS: &[(u64, &str)] = &[
+    (1, "0xce01f8eee7E479C928F8919abD53E553a36CeF67"),
+    (11155111, "0xc8Af999e38273D658BE1b921b88A9Ddf005769cC"),
+];
+
+/// UniversalResolver errors that mean the name has no (wildcard) resolver.
+const RESOLVER_NOT_FOUND_ERRORS: &[&str] = &[
+    "ResolverNotFound()",
+    "ResolverNotFound(bytes)",
+    "ResolverWildcardNotSupported()",
+    "ResolverNotContract(bytes)",
+    "ResolverNotContract(bytes,address)",
+];
+
+/// How names are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResolverBackend {
+    /// Registry, then resolver lookups, one `eth_call` each.
+    #[default]
+    Registry,
+    /// A single `resolve(bytes,bytes)` on the UniversalResolver at this address, which handles
+    /// the wildcard lookup itself.
+    UniversalResolver(Address),
+}
+
+/// The known UniversalResolver deployment on `chain_id`.
+pub fn universal_resolver_address(chain_id: u64) -> Option<Address> {
+    UNIVERSAL_RESOLVERS
+        .iter()
+        .find(|(x, _)| *x == chain_id)
+        .and_then(|(_, address)| address.parse().ok())
+}
+
+/// Whether a failed UniversalResolver call reverted because the name has no resolver.
+pub fn is_resolver_not_found(error: &DnsError) -> bool {
+    let DnsError::EthersProviderError(error) = error else {
+        return false;
+    };
+    let Some(revert) = ccip::revert_data(error) else {
+        return false;
+    };
+    RESOLVER_NOT_FOUND_ERRORS
+        .iter()
+        .any(|x| revert.starts_with(&ethers::utils::id(x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_universal_resolver_address() {
+        assert!(universal_resolver_address(1).is_some());
+        assert!(universal_resolver_address(11155111).is_some());
+        assert_eq!(universal_resolver_address(31337), None);
+    }
+}
@@ -0,0 +1,217 @@
+//! Synthetic light client updates of a test network with the minimal preset, 32 member sync
+//! committees and 64 slot periods, all forks up to Fulu active from genesis. The committees sign
+//! with deterministic keys, `generate_fixtures` writes the JSON files again. Nothing here is
+//! recorded from a real chain, the fork versions, genesis validators roots and fork epochs of
+//! the mainnet, sepolia and holesky configs are not covered by any test.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use blst::min_pk::{AggregatePublicKey, AggregateSignature, SecretKey};
+use ethers::types::{Address, Bytes, H256, U256};
+use ethers::utils::keccak256;
+
+use super::network::{Fork, ForkName, NetworkConfig, SECONDS_PER_SLOT};
+use super::types::*;
+use super::{ssz, state_gindices, LightClient, Store, BLS_DST, EXECUTION_PAYLOAD_GINDEX};
+
+/// A wall clock slot after every recorded update.
+pub const CURRENT_SLOT: u64 = 200;
+
+pub fn network() -> NetworkConfig {
+    use ForkName::*;
+    let forks = [Phase0, Altair, Bellatrix, Capella, Deneb, Electra, Fulu]
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| Fork { name, version: [i as u8, 0, 0, 1], epoch: 0 })
+        .collect();
+    NetworkConfig {
+        genesis_time: 0,
+        genesis_validators_root: H256::repeat_byte(0x42),
+        forks,
+        sync_committee_size: 32,
+        slots_per_epoch: 8,
+        epochs_per_sync_committee_period: 8,
+    }
+}
+
+pub fn bootstrap() -> LightClientBootstrap {
+    serde_json::from_str::<Versioned<_>>(include_str!("bootstrap.json")).unwrap().data
+}
+
+/// Sync committee updates of periods 0 and 1.
+pub fn updates() -> Vec<LightClientUpdate> {
+    serde_json::from_str::<Vec<Versioned<_>>>(include_str!("updates.json")).unwrap().into_iter().map(|x| x.data).collect()
+}
+
+/// Finalizes the first slot of period 2.
+pub fn finality_update() -> LightClientUpdate {
+    serde_json::from_str::<Versioned<_>>(include_str!("finality_update.json")).unwrap().data
+}
+
+/// A light client that replayed all fixtures, its finalized execution block is 1128. The network
+/// started so that the wall clock is at `CURRENT_SLOT`.
+pub fn light_client(max_age: Duration) -> LightClient {
+    let network = network();
+    let bootstrap = bootstrap();
+    let mut store = Store::bootstrap(&network, bootstrap.header.beacon.hash_tree_root(), bootstrap).unwrap();
+    for update in updates().iter().chain([&finality_update()]) {
+        store.process_update(&network, update, CURRENT_SLOT).unwrap();
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let network = NetworkConfig { genesis_time: now - CURRENT_SLOT * SECONDS_PER_SLOT, ..network };
+    LightClient { network, beacon_api: String::new(), client: reqwest::Client::new(), store: store.into(), max_age }
+}
+
+/// A merkle tree of which only some nodes matter, all other leaves are zero.
+struct SparseTree {
+    depth: u32,
+    leaves: HashMap<u64, H256>,
+}
+
+impl SparseTree {
+    fn new(depth: u32, leaves: &[(u64, H256)]) -> Self {
+        SparseTree { depth, leaves: leaves.iter().copied().collect() }
+    }
+
+    fn node(&self, gindex: u64) -> H256 {
+        match self.leaves.get(&gindex) {
+            Some(x) => *x,
+            None if gindex.ilog2() >= self.depth => H256::zero(),
+            None => ssz::hash(self.node(gindex * 2).as_bytes(), self.node(gindex * 2 + 1).as_bytes()),
+        }
+    }
+
+    fn root(&self) -> H256 {
+        self.node(1)
+    }
+
+    fn branch(&self, mut gindex: u64) -> Vec<H256> {
+        let mut branch = vec![];
+        while gindex > 1 {
+            branch.push(self.node(gindex ^ 1));
+            gindex /= 2;
+        }
+        branch
+    }
+}
+
+fn secret_keys(period: u64) -> Vec<SecretKey> {
+    (0..network().sync_committee_size)
+        .map(|i| SecretKey::key_gen(&keccak256([period.to_be_bytes(), (i as u64).to_be_bytes()].concat()), &[]).unwrap())
+        .collect()
+}
+
+fn sync_committee(keys: &[SecretKey]) -> SyncCommittee {
+    let pubkeys: Vec<_> = keys.iter().map(|x| x.sk_to_pk()).collect();
+    let aggregate = AggregatePublicKey::aggregate(&pubkeys.iter().collect::<Vec<_>>(), false).unwrap();
+    SyncCommittee {
+        pubkeys: pubkeys.iter().map(|x| Bytes::from(x.compress().to_vec())).collect(),
+        aggregate_pubkey: aggregate.to_public_key().compress().to_vec().into(),
+    }
+}
+
+fn root_of(label: &str, slot: u64) -> H256 {
+    keccak256(format!("{} {}", label, slot)).into()
+}
+
+/// The header of the block at `slot`, execution block `1000 + slot`.
+fn header(slot: u64, state_root: H256) -> LightClientHeader {
+    let execution = ExecutionPayloadHeader {
+        parent_hash: root_of("execution parent", slot),
+        fee_recipient: Address::repeat_byte(0x11),
+        state_root: root_of("execution state", slot),
+        receipts_root: root_of("receipts", slot),
+        logs_bloom: vec![0; 256].into(),
+        prev_randao: root_of("randao", slot),
+        block_number: 1000 + slot,
+        gas_limit: 30_000_000,
+        gas_used: 21_000,
+        timestamp: slot * 12,
+        extra_data: b"limo".to_vec().into(),
+        base_fee_per_gas: U256::exp10(10),
+        block_hash: root_of("execution block", slot),
+        transactions_root: root_of("transactions", slot),
+        withdrawals_root: root_of("withdrawals", slot),
+        blob_gas_used: Some(0),
+        excess_blob_gas: Some(0),
+    };
+    let body = SparseTree::new(4, &[(EXECUTION_PAYLOAD_GINDEX, execution.hash_tree_root(true).unwrap())]);
+    LightClientHeader {
+        beacon: BeaconBlockHeader {
+            slot,
+            proposer_index: slot % 7,
+            parent_root: root_of("beacon parent", slot),
+            state_root,
+            body_root: body.root(),
+        },
+        execution,
+        execution_branch: body.branch(EXECUTION_PAYLOAD_GINDEX),
+    }
+}
+
+/// An update finalizing `finalized_slot`, signed by all but two members of the committee of the
+/// signature slot's period.
+fn update(attested_slot: u64, finalized_slot: u64, signature_slot: u64, next: Option<&SyncCommittee>) -> LightClientUpdate {
+    let network = network();
+    let (finalized_gindex, _, next_gindex) = state_gindices(ForkName::Fulu);
+    let finalized_header = header(finalized_slot, root_of("beacon state", finalized_slot));
+    let mut leaves = vec![(finalized_gindex, finalized_header.beacon.hash_tree_root())];
+    leaves.extend(next.map(|x| (next_gindex, x.hash_tree_root())));
+    let state = SparseTree::new(7, &leaves);
+    let attested_header = header(attested_slot, state.root());
+
+    let signing_root = ssz::hash(
+        attested_header.beacon.hash_tree_root().as_bytes(),
+        network.sync_committee_domain(signature_slot).as_bytes(),
+    );
+    let keys = secret_keys(network.period(signature_slot));
+    let signers = &keys[..keys.len() - 2];
+    let signatures: Vec<_> = signers.iter().map(|x| x.sign(signing_root.as_bytes(), BLS_DST, &[])).collect();
+    let signature = AggregateSignature::aggregate(&signatures.iter().collect::<Vec<_>>(), false).unwrap();
+    let mut bits = vec![0u8; keys.len() / 8];
+    for i in 0..signers.len() {
+        bits[i / 8] |= 1 << (i % 8);
+    }
+
+    LightClientUpdate {
+        attested_header,
+        next_sync_committee: next.cloned(),
+        next_sync_committee_branch: next.map(|_| state.branch(next_gindex)),
+        finalized_header,
+        finality_branch: state.branch(finalized_gindex),
+        sync_aggregate: SyncAggregate {
+            sync_committee_bits: bits.into(),
+            sync_committee_signature: signature.to_signature().compress().to_vec().into(),
+        },
+        signature_slot,
+    }
+}
+
+fn versioned<T>(data: T) -> Versioned<T> {
+    Versioned { version: "fulu".to_string(), data }
+}
+
+#[test]
+#[ignore]
+fn generate_fixtures() {
+    let committees: Vec<_> = (0..3).map(|x| sync_committee(&secret_keys(x))).collect();
+    let (_, current_gindex, _) = state_gindices(ForkName::Fulu);
+    let state = SparseTree::new(7, &[(current_gindex, committees[0].hash_tree_root())]);
+    let bootstrap = LightClientBootstrap {
+        header: header(8, state.root()),
+        current_sync_committee: committees[0].clone(),
+        current_sync_committee_branch: state.branch(current_gindex),
+    };
+    let updates = vec![
+        versioned(update(40, 32, 41, Some(&committees[1]))),
+        versioned(update(104, 96, 105, Some(&committees[2]))),
+    ];
+    let finality_update = update(140, 128, 141, None);
+
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/light_client/fixtures/");
+    let write = |name: &str, json: String| std::fs::write(format!("{}{}", dir, name), json + "\n").unwrap();
+    write("bootstrap.json", serde_json::to_string_pretty(&versioned(bootstrap)).unwrap());
+    write("updates.json", serde_json::to_string_pretty(&updates).unwrap());
+    write("finality_update.json", serde_json::to_string_pretty(&versioned(finality_update)).unwrap());
+}
@@ -0,0 +1,399 @@
+use std::fmt::Display;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use blst::min_pk::{PublicKey, Signature};
+use blst::BLST_ERROR;
+use ethers::types::H256;
+use serde::de::DeserializeOwned;
+
+use self::network::{ForkName, NetworkConfig, SECONDS_PER_SLOT};
+use self::types::{ExecutionPayloadHeader, LightClientBootstrap, LightClientHeader, LightClientUpdate, SyncCommittee, Versioned};
+
+pub mod network;
+pub mod ssz;
+pub mod types;
+#[cfg(test)]
+pub(crate) mod fixtures;
+
+/// `BeaconBlockBody.execution_payload`, the same since Capella.
+const EXECUTION_PAYLOAD_GINDEX: u64 = 25;
+const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Most updates the beacon API serves per request.
+const MAX_REQUEST_LIGHT_CLIENT_UPDATES: u64 = 128;
+const SYNC_INTERVAL: Duration = Duration::from_secs(12);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Generalized indices of the finalized checkpoint root, current and next sync committee in
+/// the `BeaconState` of a fork, Electra added fields that moved them one level down.
+fn state_gindices(fork: ForkName) -> (u64, u64, u64) {
+    match fork {
+        x if x >= ForkName::Electra => (169, 86, 87),
+        _ => (105, 54, 55),
+    }
+}
+
+#[derive(Debug)]
+pub enum LightClientError {
+    Request(String),
+    /// Light client headers before Capella carry no execution payload header.
+    UnsupportedFork(u64),
+    MalformedHeader(u64),
+    InvalidBranch(&'static str),
+    /// The bootstrap is for a block with a different root than the checkpoint.
+    CheckpointMismatch(H256),
+    MalformedSyncCommittee,
+    ConflictingSyncCommittee,
+    /// The sync committee of this period is not known yet.
+    UnknownSyncCommittee(u64),
+    InvalidSlots { attested: u64, finalized: u64, signature: u64 },
+    InsufficientParticipation(usize, usize),
+    InvalidSignature,
+}
+
+impl Display for LightClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LightClientError::Request(e) => write!(f, "beacon API request failed: {}", e),
+            LightClientError::UnsupportedFork(slot) => write!(f, "unsupported fork at slot {}", slot),
+            LightClientError::MalformedHeader(slot) => write!(f, "malformed execution payload header at slot {}", slot),
+            LightClientError::InvalidBranch(x) => write!(f, "invalid {} merkle branch", x),
+            LightClientError::CheckpointMismatch(root) => write!(f, "bootstrap block root {:?} is not the checkpoint", root),
+            LightClientError::MalformedSyncCommittee => write!(f, "malformed sync committee"),
+            LightClientError::ConflictingSyncCommittee => write!(f, "next sync committee conflicts with the known one"),
+            LightClientError::UnknownSyncCommittee(period) => write!(f, "unknown sync committee of period {}", period),
+            LightClientError::InvalidSlots { attested, finalized, signature } => {
+                write!(f, "invalid slots, attested {} finalized {} signature {}", attested, finalized, signature)
+            }
+            LightClientError::InsufficientParticipation(signers, size) => {
+                write!(f, "{} of {} sync committee members signed, short of a supermajority", signers, size)
+            }
+            LightClientError::InvalidSignature => write!(f, "invalid sync committee signature"),
+        }
+    }
+}
+
+impl std::error::Error for LightClientError {}
+
+/// A sync committee with its public keys checked and decompressed.
+#[derive(Debug)]
+struct Committee {
+    root: H256,
+    pubkeys: Vec<PublicKey>,
+}
+
+impl Committee {
+    fn new(network: &NetworkConfig, committee: &SyncCommittee) -> Result<Self, LightClientError> {
+        if committee.pubkeys.len() != network.sync_committee_size {
+            return Err(LightClientError::MalformedSyncCommittee);
+        }
+        let pubkeys = committee
+            .pubkeys
+            .iter()
+            .map(|x| PublicKey::key_validate(x))
+            .collect::<Result<_, _>>()
+            .map_err(|_| LightClientError::MalformedSyncCommittee)?;
+        Ok(Committee { root: committee.hash_tree_root(), pubkeys })
+    }
+}
+
+/// Checks the execution payload header against the beacon block body root.
+fn verify_header(network: &NetworkConfig, header: &LightClientHeader) -> Result<(), LightClientError> {
+    let slot = header.beacon.slot;
+    let fork = network.fork_at(slot).name;
+    if fork < ForkName::Capella {
+        return Err(LightClientError::UnsupportedFork(slot));
+    }
+    let root = header
+        .execution
+        .hash_tree_root(fork >= ForkName::Deneb)
+        .ok_or(LightClientError::MalformedHeader(slot))?;
+    if !ssz::is_valid_merkle_branch(root, &header.execution_branch, EXECUTION_PAYLOAD_GINDEX, header.beacon.body_root) {
+        return Err(LightClientError::InvalidBranch("execution payload"));
+    }
+    Ok(())
+}
+
+/// What the light client trusts: the latest finalized header and the sync committees that sign
+/// the headers of the current and next period.
+#[derive(Debug)]
+struct Store {
+    finalized_header: LightClientHeader,
+    current_sync_committee: Committee,
+    next_sync_committee: Option<Committee>,
+}
+
+impl Store {
+    fn bootstrap(network: &NetworkConfig, checkpoint: H256, bootstrap: LightClientBootstrap) -> Result<Self, LightClientError> {
+        let header = bootstrap.header;
+        let root = header.beacon.hash_tree_root();
+        if root != checkpoint {
+            return Err(LightClientError::CheckpointMismatch(root));
+        }
+        verify_header(network, &header)?;
+        let (_, current_gindex, _) = state_gindices(network.fork_at(header.beacon.slot).name);
+        let committee = &bootstrap.current_sync_committee;
+        if !ssz::is_valid_merkle_branch(committee.hash_tree_root(), &bootstrap.current_sync_committee_branch, current_gindex, header.beacon.state_root) {
+            return Err(LightClientError::InvalidBranch("current sync committee"));
+        }
+        Ok(Store {
+            current_sync_committee: Committee::new(network, committee)?,
+            next_sync_committee: None,
+            finalized_header: header,
+        })
+    }
+
+    /// Validates `update` and applies it when it finalizes a newer header or makes the next sync
+    /// committee known, `false` for updates with nothing new. Only updates with finality and a
+    /// supermajority of the sync committee are accepted.
+    fn process_update(&mut self, network: &NetworkConfig, update: &LightClientUpdate, current_slot: u64) -> Result<bool, LightClientError> {
+        let attested = &update.attested_header;
+        let finalized = &update.finalized_header;
+        let (attested_slot, finalized_slot, signature_slot) = (attested.beacon.slot, finalized.beacon.slot, update.signature_slot);
+        if !(current_slot >= signature_slot && signature_slot > attested_slot && attested_slot >= finalized_slot) {
+            return Err(LightClientError::InvalidSlots { attested: attested_slot, finalized: finalized_slot, signature: signature_slot });
+        }
+        let store_period = network.period(self.finalized_header.beacon.slot);
+        let attested_period = network.period(attested_slot);
+        let finalized_period = network.period(finalized_slot);
+        let signature_period = network.period(signature_slot);
+
+        let committee = match &self.next_sync_committee {
+            _ if signature_period == store_period => &self.current_sync_committee,
+            Some(next) if signature_period == store_period + 1 => next,
+            _ => return Err(LightClientError::UnknownSyncCommittee(signature_period)),
+        };
+        let adds_next = self.next_sync_committee.is_none() && update.next_sync_committee.is_some() && attested_period == finalized_period;
+        if finalized_slot <= self.finalized_header.beacon.slot && !adds_next {
+            return Ok(false);
+        }
+
+        verify_header(network, attested)?;
+        verify_header(network, finalized)?;
+        let (finalized_gindex, _, next_gindex) = state_gindices(network.fork_at(attested_slot).name);
+        let state_root = attested.beacon.state_root;
+        if !ssz::is_valid_merkle_branch(finalized.beacon.hash_tree_root(), &update.finality_branch, finalized_gindex, state_root) {
+            return Err(LightClientError::InvalidBranch("finality"));
+        }
+        let next = match (&update.next_sync_committee, &update.next_sync_committee_branch) {
+            (Some(next), Some(branch)) => {
+                let root = next.hash_tree_root();
+                if !ssz::is_valid_merkle_branch(root, branch, next_gindex, state_root) {
+                    return Err(LightClientError::InvalidBranch("next sync committee"));
+                }
+                if let Some(known) = &self.next_sync_committee {
+                    if attested_period == store_period && known.root != root {
+                        return Err(LightClientError::ConflictingSyncCommittee);
+                    }
+                }
+                // the next sync committee of the period the store moves to
+                (attested_period == finalized_period).then_some(next)
+            }
+            _ => None,
+        };
+
+        let size = network.sync_committee_size;
+        let aggregate = &update.sync_aggregate;
+        if aggregate.sync_committee_bits.len() * 8 != size {
+            return Err(LightClientError::InsufficientParticipation(0, size));
+        }
+        let signers: Vec<&PublicKey> = (0..size).filter(|x| aggregate.participated(*x)).map(|x| &committee.pubkeys[x]).collect();
+        if signers.len() * 3 < size * 2 {
+            return Err(LightClientError::InsufficientParticipation(signers.len(), size));
+        }
+        let signing_root = ssz::hash(attested.beacon.hash_tree_root().as_bytes(), network.sync_committee_domain(signature_slot).as_bytes());
+        let signature = Signature::sig_validate(&aggregate.sync_committee_signature, true).map_err(|_| LightClientError::InvalidSignature)?;
+        if signature.fast_aggregate_verify(false, signing_root.as_bytes(), BLS_DST, &signers) != BLST_ERROR::BLST_SUCCESS {
+            return Err(LightClientError::InvalidSignature);
+        }
+
+        let next = next.map(|x| Committee::new(network, x)).transpose()?;
+        if self.next_sync_committee.is_none() {
+            self.next_sync_committee = next;
+        } else if finalized_period == store_period + 1 {
+            self.current_sync_committee = self.next_sync_committee.take().unwrap();
+            self.next_sync_committee = next;
+        }
+        if finalized_slot > self.finalized_header.beacon.slot {
+            self.finalized_header = finalized.clone();
+        }
+        Ok(true)
+    }
+}
+
+/// Where the light client follows the beacon chain from.
+#[derive(Debug, Clone)]
+pub struct LightClientConfig {
+    pub beacon_api: String,
+    /// Block root of a recent finalized beacon block.
+    pub checkpoint: H256,
+    pub network: NetworkConfig,
+    /// Age after which the finalized header is no longer used, e.g. when syncing keeps failing.
+    pub max_age: Duration,
+}
+
+/// Follows the beacon chain sync committee from a trusted checkpoint through a beacon API and
+/// keeps the execution header of the latest finalized block, whose state root storage proofs
+/// can be verified against without trusting the RPC endpoints.
+#[derive(Debug)]
+pub struct LightClient {
+    network: NetworkConfig,
+    beacon_api: String,
+    client: reqwest::Client,
+    store: RwLock<Store>,
+    max_age: Duration,
+}
+
+impl LightClient {
+    /// Bootstraps from the block with root `checkpoint`, which has to be recent enough that its
+    /// sync committee is still around, a few months at most.
+    pub async fn new(config: LightClientConfig) -> Result<Self, LightClientError> {
+        let LightClientConfig { beacon_api, checkpoint, network, max_age } = config;
+        let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().map_err(|e| LightClientError::Request(e.to_string()))?;
+        let path = format!("/eth/v1/beacon/light_client/bootstrap/{:?}", checkpoint);
+        let bootstrap: Versioned<LightClientBootstrap> = get(&client, &beacon_api, &path).await?;
+        let store = Store::bootstrap(&network, checkpoint, bootstrap.data)?;
+        Ok(LightClient { network, beacon_api, client, store: RwLock::new(store), max_age })
+    }
+
+    /// The execution payload header of the latest finalized block.
+    pub fn finalized_execution(&self) -> ExecutionPayloadHeader {
+        self.store.read().unwrap().finalized_header.execution.clone()
+    }
+
+    pub fn finalized_slot(&self) -> u64 {
+        self.store.read().unwrap().finalized_header.beacon.slot
+    }
+
+    /// The execution payload header of the latest finalized block, `None` once that block is
+    /// older than the maximum age.
+    pub fn fresh_finalized_execution(&self) -> Option<ExecutionPayloadHeader> {
+        let store = self.store.read().unwrap();
+        let age = self.network.current_slot().saturating_sub(store.finalized_header.beacon.slot) * SECONDS_PER_SLOT;
+        (age <= self.max_age.as_secs()).then(|| store.finalized_header.execution.clone())
+    }
+
+    fn process(&self, update: &LightClientUpdate, current_slot: u64) -> Result<(), LightClientError> {
+        let mut store = self.store.write().unwrap();
+        if store.process_update(&self.network, update, current_slot)? {
+            let header = &store.finalized_header;
+            println!(
+                "Light client finalized slot {}, execution block {} {:?}",
+                header.beacon.slot, header.execution.block_number, header.execution.block_hash
+            );
+        }
+        Ok(())
+    }
+
+    /// Catches up on the sync committee periods since the finalized header, then applies the
+    /// latest finality update.
+    async fn sync(&self) -> Result<(), LightClientError> {
+        let current_slot = self.network.current_slot();
+        let store_period = self.network.period(self.finalized_slot());
+        let current_period = self.network.period(current_slot);
+        if current_period > store_period || self.store.read().unwrap().next_sync_committee.is_none() {
+            let count = (current_period.saturating_sub(store_period) + 1).min(MAX_REQUEST_LIGHT_CLIENT_UPDATES);
+            let path = format!("/eth/v1/beacon/light_client/updates?start_period={}&count={}", store_period, count);
+            let updates: Vec<Versioned<LightClientUpdate>> = get(&self.client, &self.beacon_api, &path).await?;
+            for update in updates {
+                self.process(&update.data, current_slot)?;
+            }
+        }
+        let update: Versioned<LightClientUpdate> = get(&self.client, &self.beacon_api, "/eth/v1/beacon/light_client/finality_update").await?;
+        self.process(&update.data, current_slot)
+    }
+
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            if let Err(e) = self.sync().await {
+                println!("Light client sync failed: {}", e);
+            }
+            tokio::time::sleep(SYNC_INTERVAL).await;
+        }
+    }
+}
+
+async fn get<T: DeserializeOwned>(client: &reqwest::Client, beacon_api: &str, path: &str) -> Result<T, LightClientError> {
+    let url = format!("{}{}", beacon_api.trim_end_matches('/'), path);
+    let error = |e: reqwest::Error| LightClientError::Request(e.to_string());
+    client.get(url).send().await.map_err(error)?.error_for_status().map_err(error)?.json().await.map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> (NetworkConfig, Store, Vec<LightClientUpdate>) {
+        let network = fixtures::network();
+        let bootstrap = fixtures::bootstrap();
+        let checkpoint = bootstrap.header.beacon.hash_tree_root();
+        let store = Store::bootstrap(&network, checkpoint, bootstrap).unwrap();
+        (network, store, fixtures::updates())
+    }
+
+    #[test]
+    fn test_replay_updates() {
+        let (network, mut store, updates) = replay();
+        for update in &updates {
+            assert!(store.process_update(&network, update, fixtures::CURRENT_SLOT).unwrap());
+        }
+        let finality_update = fixtures::finality_update();
+        assert!(store.process_update(&network, &finality_update, fixtures::CURRENT_SLOT).unwrap());
+        // the finality update crossed into the period of the last known committee
+        assert_eq!(store.finalized_header, finality_update.finalized_header);
+        assert_eq!(Some(store.current_sync_committee.root), updates[1].next_sync_committee.as_ref().map(|x| x.hash_tree_root()));
+        assert!(store.next_sync_committee.is_none());
+        // replays have nothing new
+        assert!(!store.process_update(&network, &finality_update, fixtures::CURRENT_SLOT).unwrap());
+    }
+
+    #[test]
+    fn test_finalized_header_max_age() {
+        // finalized at slot 128, the wall clock is 72 slots later
+        assert!(fixtures::light_client(Duration::from_secs(3600)).fresh_finalized_execution().is_some());
+        assert!(fixtures::light_client(Duration::from_secs(600)).fresh_finalized_execution().is_none());
+    }
+
+    #[test]
+    fn test_rejects_invalid_updates() {
+        let (network, mut store, updates) = replay();
+        let bootstrap = fixtures::bootstrap();
+        let res = Store::bootstrap(&network, H256::zero(), bootstrap);
+        assert!(matches!(res, Err(LightClientError::CheckpointMismatch(_))));
+
+        // signed by the committee of a period whose committee is not known yet
+        let res = store.process_update(&network, &fixtures::finality_update(), fixtures::CURRENT_SLOT);
+        assert!(matches!(res, Err(LightClientError::UnknownSyncCommittee(2))));
+        let res = store.process_update(&network, &updates[0], updates[0].signature_slot - 1);
+        assert!(matches!(res, Err(LightClientError::InvalidSlots { .. })));
+
+        let mut update = updates[0].clone();
+        update.finality_branch[0] = H256::repeat_byte(1);
+        let res = store.process_update(&network, &update, fixtures::CURRENT_SLOT);
+        assert!(matches!(res, Err(LightClientError::InvalidBranch("finality"))));
+
+        let mut update = updates[0].clone();
+        update.finalized_header.execution.state_root = H256::repeat_byte(1);
+        let res = store.process_update(&network, &update, fixtures::CURRENT_SLOT);
+        assert!(matches!(res, Err(LightClientError::InvalidBranch("execution payload"))));
+
+        let mut update = updates[0].clone();
+        update.next_sync_committee = updates[1].next_sync_committee.clone();
+        let res = store.process_update(&network, &update, fixtures::CURRENT_SLOT);
+        assert!(matches!(res, Err(LightClientError::InvalidBranch("next sync committee"))));
+
+        let mut update = updates[0].clone();
+        update.attested_header.beacon.proposer_index += 1;
+        let res = store.process_update(&network, &update, fixtures::CURRENT_SLOT);
+        assert!(matches!(res, Err(LightClientError::InvalidSignature)));
+
+        let mut update = updates[0].clone();
+        update.sync_aggregate.sync_committee_bits = vec![0xff, 0xff, 0x1f, 0].into();
+        let res = store.process_update(&network, &update, fixtures::CURRENT_SLOT);
+        assert!(matches!(res, Err(LightClientError::InsufficientParticipation(21, 32))));
+
+        // nothing of the rejected updates was applied
+        assert!(store.next_sync_committee.is_none());
+        assert!(store.process_update(&network, &updates[0], fixtures::CURRENT_SLOT).unwrap());
+    }
+}
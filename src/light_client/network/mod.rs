@@ -0,0 +1,152 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::types::{Chain, H256};
+
+use super::ssz;
+
+pub const SECONDS_PER_SLOT: u64 = 12;
+const DOMAIN_SYNC_COMMITTEE: [u8; 4] = [7, 0, 0, 0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForkName {
+    Phase0,
+    Altair,
+    Bellatrix,
+    Capella,
+    Deneb,
+    Electra,
+    Fulu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fork {
+    pub name: ForkName,
+    pub version: [u8; 4],
+    pub epoch: u64,
+}
+
+/// The consensus parameters of a network the light client follows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub genesis_time: u64,
+    pub genesis_validators_root: H256,
+    /// Oldest first.
+    pub forks: Vec<Fork>,
+    pub sync_committee_size: usize,
+    pub slots_per_epoch: u64,
+    pub epochs_per_sync_committee_period: u64,
+}
+
+fn fork(name: ForkName, version: u32, epoch: u64) -> Fork {
+    Fork { name, version: version.to_be_bytes(), epoch }
+}
+
+fn mainnet_preset(genesis_time: u64, genesis_validators_root: &str, forks: Vec<Fork>) -> NetworkConfig {
+    NetworkConfig {
+        genesis_time,
+        genesis_validators_root: genesis_validators_root.parse().unwrap(),
+        forks,
+        sync_committee_size: 512,
+        slots_per_epoch: 32,
+        epochs_per_sync_committee_period: 256,
+    }
+}
+
+impl NetworkConfig {
+    /// The beacon chain of `chain_id`, `None` for chains without a known one.
+    pub fn for_chain(chain_id: u64) -> Option<Self> {
+        use ForkName::*;
+        match Chain::try_from(chain_id).ok()? {
+            Chain::Mainnet => Some(mainnet_preset(
+                1606824023,
+                "0x4b363db94e286120d76eb905340fdd4e54bfe9f06bf33ff6cf5ad27f511bfe95",
+                vec![
+                    fork(Phase0, 0x00000000, 0),
+                    fork(Altair, 0x01000000, 74240),
+                    fork(Bellatrix, 0x02000000, 144896),
+                    fork(Capella, 0x03000000, 194048),
+                    fork(Deneb, 0x04000000, 269568),
+                    fork(Electra, 0x05000000, 364032),
+                    fork(Fulu, 0x06000000, 411392),
+                ],
+            )),
+            Chain::Sepolia => Some(mainnet_preset(
+                1655733600,
+                "0xd8ea171f3c94aea21ebc42a1ed61052acf3f9209c00e4efbaaddac09ed9b8078",
+                vec![
+                    fork(Phase0, 0x90000069, 0),
+                    fork(Altair, 0x90000070, 50),
+                    fork(Bellatrix, 0x90000071, 100),
+                    fork(Capella, 0x90000072, 56832),
+                    fork(Deneb, 0x90000073, 132608),
+                    fork(Electra, 0x90000074, 222464),
+                    fork(Fulu, 0x90000075, 272640),
+                ],
+            )),
+            Chain::Holesky => Some(mainnet_preset(
+                1695902400,
+                "0x9143aa7c615a7f7115e2b6aac319c03529df8242ae705fba9df39b79c59fa8b1",
+                vec![
+                    fork(Phase0, 0x01017000, 0),
+                    fork(Altair, 0x02017000, 0),
+                    fork(Bellatrix, 0x03017000, 0),
+                    fork(Capella, 0x04017000, 256),
+                    fork(Deneb, 0x05017000, 29696),
+                    fork(Electra, 0x06017000, 115968),
+                    fork(Fulu, 0x07017000, 165120),
+                ],
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn slots_per_period(&self) -> u64 {
+        self.slots_per_epoch * self.epochs_per_sync_committee_period
+    }
+
+    pub fn period(&self, slot: u64) -> u64 {
+        slot / self.slots_per_period()
+    }
+
+    pub fn fork_at(&self, slot: u64) -> Fork {
+        let epoch = slot / self.slots_per_epoch;
+        *self.forks.iter().rev().find(|x| x.epoch <= epoch).unwrap_or(&self.forks[0])
+    }
+
+    /// The slot of the wall clock.
+    pub fn current_slot(&self) -> u64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        now.saturating_sub(self.genesis_time) / SECONDS_PER_SLOT
+    }
+
+    /// Domain of sync committee signatures made at `signature_slot`, whose fork is the one of
+    /// the slot before.
+    pub fn sync_committee_domain(&self, signature_slot: u64) -> H256 {
+        let fork = self.fork_at(signature_slot.max(1) - 1);
+        let mut version = H256::zero();
+        version[..4].copy_from_slice(&fork.version);
+        let fork_data_root = ssz::hash(version.as_bytes(), self.genesis_validators_root.as_bytes());
+        let mut domain = H256::zero();
+        domain[..4].copy_from_slice(&DOMAIN_SYNC_COMMITTEE);
+        domain[4..].copy_from_slice(&fork_data_root[..28]);
+        domain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_config() {
+        let mainnet = NetworkConfig::for_chain(1).unwrap();
+        assert_eq!(mainnet.period(8191), 0);
+        assert_eq!(mainnet.period(8192), 1);
+        assert_eq!(mainnet.fork_at(269568 * 32 - 1).name, ForkName::Capella);
+        assert_eq!(mainnet.fork_at(269568 * 32).name, ForkName::Deneb);
+        // signatures of the first Deneb slot are made with the Capella fork version
+        assert_ne!(mainnet.sync_committee_domain(269568 * 32), mainnet.sync_committee_domain(269568 * 32 + 1));
+        assert!(NetworkConfig::for_chain(17000).is_some());
+        assert_eq!(NetworkConfig::for_chain(31337), None);
+    }
+}
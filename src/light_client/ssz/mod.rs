@@ -0,0 +1,84 @@
+use ethers::types::{H256, U256};
+use sha2::{Digest, Sha256};
+
+/// `sha256(a ++ b)`, the node hash of SSZ merkle trees.
+pub fn hash(a: &[u8], b: &[u8]) -> H256 {
+    H256::from_slice(&Sha256::new().chain_update(a).chain_update(b).finalize())
+}
+
+/// Root of the tree over `chunks`, padded with zero chunks to a power of two.
+pub fn merkleize(mut chunks: Vec<H256>) -> H256 {
+    chunks.resize(chunks.len().max(1).next_power_of_two(), H256::zero());
+    while chunks.len() > 1 {
+        chunks = chunks.chunks(2).map(|x| hash(x[0].as_bytes(), x[1].as_bytes())).collect();
+    }
+    chunks[0]
+}
+
+/// Root of a list, its elements' root mixed with the list length.
+pub fn mix_in_length(root: H256, length: usize) -> H256 {
+    hash(root.as_bytes(), uint64(length as u64).as_bytes())
+}
+
+pub fn uint64(value: u64) -> H256 {
+    let mut chunk = H256::zero();
+    chunk[..8].copy_from_slice(&value.to_le_bytes());
+    chunk
+}
+
+pub fn uint256(value: U256) -> H256 {
+    let mut chunk = H256::zero();
+    value.to_little_endian(chunk.as_bytes_mut());
+    chunk
+}
+
+/// Root of a fixed size byte vector, packed into zero padded chunks.
+pub fn bytes(value: &[u8]) -> H256 {
+    merkleize(
+        value
+            .chunks(32)
+            .map(|x| {
+                let mut chunk = H256::zero();
+                chunk[..x.len()].copy_from_slice(x);
+                chunk
+            })
+            .collect(),
+    )
+}
+
+/// Whether `branch` proves `leaf` at generalized index `gindex` of the tree with `root`.
+pub fn is_valid_merkle_branch(leaf: H256, branch: &[H256], gindex: u64, root: H256) -> bool {
+    let depth = gindex.ilog2() as usize;
+    if branch.len() != depth {
+        return false;
+    }
+    let mut node = leaf;
+    for (i, sibling) in branch.iter().enumerate() {
+        node = match (gindex >> i) & 1 {
+            1 => hash(sibling.as_bytes(), node.as_bytes()),
+            _ => hash(node.as_bytes(), sibling.as_bytes()),
+        };
+    }
+    node == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkleize() {
+        let chunks: Vec<H256> = (1..=3).map(uint64).collect();
+        let left = hash(chunks[0].as_bytes(), chunks[1].as_bytes());
+        let right = hash(chunks[2].as_bytes(), H256::zero().as_bytes());
+        let root = merkleize(chunks.clone());
+        assert_eq!(root, hash(left.as_bytes(), right.as_bytes()));
+        assert_eq!(merkleize(vec![]), H256::zero());
+        assert_eq!(bytes(&[1; 48]), hash(&[1; 32], &[[1; 16], [0; 16]].concat()));
+
+        // generalized index 6 is the left child of the right child of the root
+        assert!(is_valid_merkle_branch(chunks[2], &[H256::zero(), left], 6, root));
+        assert!(!is_valid_merkle_branch(chunks[2], &[H256::zero(), left], 7, root));
+        assert!(!is_valid_merkle_branch(chunks[2], &[H256::zero()], 6, root));
+    }
+}
@@ -0,0 +1,195 @@
+use ethers::types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+
+use super::ssz;
+
+/// Beacon API responses wrap their payload with the fork it was produced at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    pub version: String,
+    pub data: T,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeaconBlockHeader {
+    #[serde(with = "quoted")]
+    pub slot: u64,
+    #[serde(with = "quoted")]
+    pub proposer_index: u64,
+    pub parent_root: H256,
+    pub state_root: H256,
+    pub body_root: H256,
+}
+
+impl BeaconBlockHeader {
+    pub fn hash_tree_root(&self) -> H256 {
+        ssz::merkleize(vec![
+            ssz::uint64(self.slot),
+            ssz::uint64(self.proposer_index),
+            self.parent_root,
+            self.state_root,
+            self.body_root,
+        ])
+    }
+}
+
+/// Capella execution payload header, Deneb added the blob gas fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionPayloadHeader {
+    pub parent_hash: H256,
+    pub fee_recipient: Address,
+    pub state_root: H256,
+    pub receipts_root: H256,
+    pub logs_bloom: Bytes,
+    pub prev_randao: H256,
+    #[serde(with = "quoted")]
+    pub block_number: u64,
+    #[serde(with = "quoted")]
+    pub gas_limit: u64,
+    #[serde(with = "quoted")]
+    pub gas_used: u64,
+    #[serde(with = "quoted")]
+    pub timestamp: u64,
+    pub extra_data: Bytes,
+    #[serde(with = "decimal")]
+    pub base_fee_per_gas: U256,
+    pub block_hash: H256,
+    pub transactions_root: H256,
+    pub withdrawals_root: H256,
+    #[serde(default, with = "quoted_option", skip_serializing_if = "Option::is_none")]
+    pub blob_gas_used: Option<u64>,
+    #[serde(default, with = "quoted_option", skip_serializing_if = "Option::is_none")]
+    pub excess_blob_gas: Option<u64>,
+}
+
+impl ExecutionPayloadHeader {
+    /// `None` for malformed headers, `deneb` tells whether the blob gas fields are part of it.
+    pub fn hash_tree_root(&self, deneb: bool) -> Option<H256> {
+        if self.logs_bloom.len() != 256 || self.extra_data.len() > 32 {
+            return None;
+        }
+        let mut fields = vec![
+            self.parent_hash,
+            ssz::bytes(self.fee_recipient.as_bytes()),
+            self.state_root,
+            self.receipts_root,
+            ssz::bytes(&self.logs_bloom),
+            self.prev_randao,
+            ssz::uint64(self.block_number),
+            ssz::uint64(self.gas_limit),
+            ssz::uint64(self.gas_used),
+            ssz::uint64(self.timestamp),
+            ssz::mix_in_length(ssz::bytes(&self.extra_data), self.extra_data.len()),
+            ssz::uint256(self.base_fee_per_gas),
+            self.block_hash,
+            self.transactions_root,
+            self.withdrawals_root,
+        ];
+        if deneb {
+            fields.push(ssz::uint64(self.blob_gas_used?));
+            fields.push(ssz::uint64(self.excess_blob_gas?));
+        }
+        Some(ssz::merkleize(fields))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LightClientHeader {
+    pub beacon: BeaconBlockHeader,
+    pub execution: ExecutionPayloadHeader,
+    pub execution_branch: Vec<H256>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncCommittee {
+    pub pubkeys: Vec<Bytes>,
+    pub aggregate_pubkey: Bytes,
+}
+
+impl SyncCommittee {
+    pub fn hash_tree_root(&self) -> H256 {
+        let pubkeys = ssz::merkleize(self.pubkeys.iter().map(|x| ssz::bytes(x)).collect());
+        ssz::merkleize(vec![pubkeys, ssz::bytes(&self.aggregate_pubkey)])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncAggregate {
+    pub sync_committee_bits: Bytes,
+    pub sync_committee_signature: Bytes,
+}
+
+impl SyncAggregate {
+    /// Whether the committee member at `index` signed.
+    pub fn participated(&self, index: usize) -> bool {
+        self.sync_committee_bits.get(index / 8).is_some_and(|x| x >> (index % 8) & 1 == 1)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightClientBootstrap {
+    pub header: LightClientHeader,
+    pub current_sync_committee: SyncCommittee,
+    pub current_sync_committee_branch: Vec<H256>,
+}
+
+/// A sync committee update or, without the next sync committee, a finality update.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightClientUpdate {
+    pub attested_header: LightClientHeader,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_sync_committee: Option<SyncCommittee>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_sync_committee_branch: Option<Vec<H256>>,
+    pub finalized_header: LightClientHeader,
+    pub finality_branch: Vec<H256>,
+    pub sync_aggregate: SyncAggregate,
+    #[serde(with = "quoted")]
+    pub signature_slot: u64,
+}
+
+/// The beacon API quotes integers.
+mod quoted {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+mod quoted_option {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(x) => super::quoted::serialize(x, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        #[derive(Deserialize)]
+        struct Quoted(#[serde(with = "super::quoted")] u64);
+        Ok(Option::<Quoted>::deserialize(deserializer)?.map(|x| x.0))
+    }
+}
+
+/// Quoted decimal, `U256` itself (de)serializes as hex.
+mod decimal {
+    use ethers::types::U256;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        U256::from_dec_str(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
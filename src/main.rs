@@ -1,121 +1,93 @@
 use clap::Parser;
-use dns::DnsName;
 use ethers::prelude::*;
 
-use async_trait::async_trait;
+use std::sync::Arc;
 use tokio::net::UdpSocket;
-use once_cell::sync::Lazy;
 
-use crate::dns::DnsError;
+use crate::ens::EthersAnswerProvider;
+use crate::light_client::LightClient;
 
 
 mod dns;
 mod cli;
-
-struct EthersAnswerProvider<T: Send + Sync> {
-    provider: ethers::providers::Provider<T>,
-}
-
-//maybe these should be prepended by something?
-const ENS_RECORD_SERVICES: Lazy<Vec<DnsName>> = Lazy::new(|| {
-    let v: Vec<String> = vec![
-    "_atproto".to_string(), //bsky
-    "avatar".to_string(),
-    "description".to_string(),
-    "display".to_string(),
-    "email".to_string(),
-    "keywords".to_string(),
-    "mail".to_string(),
-    "notice".to_string(),
-    "location".to_string(),
-    "phone".to_string(),
-    "url".to_string(),
-    "com.github".to_string(),
-    "com.peepeth".to_string(),
-    "com.linkedin".to_string(),
-    "com.twitter".to_string(),
-    "io.keybase".to_string(),
-    "org.telegram".to_string()
-    ];
-    
-    v.iter().map(|x| DnsName::from(x.to_string())).collect()
-});
-
-#[async_trait]
-impl<'a, T: Send + Sync + JsonRpcClient> dns::DnsAnswerProvider for EthersAnswerProvider<T> {
-    async fn get_answer_async(&self, question: dns::DnsQuestion) -> Option<String> {
-        let binding = ENS_RECORD_SERVICES;
-        let svcname_dnsrecord_a = DnsName::from("A".to_string());
-        let svcname_dnsrecord_aaaa = DnsName::from("AAAA".to_string());
-
-        let svc: Option<&DnsName> = match question.qtype {
-            1 => {
-                Some(&svcname_dnsrecord_a)
-            },
-            28 => {
-                Some(&svcname_dnsrecord_aaaa)
-            },
-            _ => {
-                binding
-                .iter()
-                .filter(|x| x.is_label_of(&question.qname))
-                .next()
-            }
-        };
-        
-        
-        println!("svc {:?}", svc);
-        let res = match svc {
-            Some(x) => {
-                let name = question.qname.clone().remove_prefix_labels(x).or(Some(question.qname.clone()))?;
-                self
-                    .provider
-                    .resolve_field(&name.punycode_decode()?, &x.punycode_decode()?)
-                    .await.map_err(DnsError::from)
-            }
-            None => Err(DnsError::ErrNoServiceTypeRecognized)
-        };
-        match res {
-            Ok(r) => if r.len() > 0 {
-                Some(r)
-            } else {
-                None
-            },
-            Err(e) => {
-                println!("error resolving {:?} {:?}", question.qname, e);
-                None
-            }
-        }
-    }
-}
+mod ens;
+mod rpc;
+mod light_client;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let opts = cli::Opts::parse();
-    let resolved_opts = cli::ResolvedOpts::<ethers::providers::Http>::try_from(opts)?;
+    let resolved_opts = cli::ResolvedOpts::<rpc::RpcPool<Http>>::try_from(opts)?;
 
     let socket = UdpSocket::bind(resolved_opts.udp_bind).await?;
     println!("Listening on: {}", socket.local_addr()?);
 
-    let block_time = resolved_opts.provider.get_block(BlockNumber::Latest).await?.map(|block| block.timestamp);
+    let head = resolved_opts.provider.get_block(BlockNumber::Latest).await?.ok_or("Head block unavailable")?;
+    let finalized = resolved_opts.provider.get_block(BlockNumber::Finalized).await;
     let chain_id = resolved_opts.provider.get_chainid().await?;
 
-    println!("Current block time: {:?}, Chain ID: {:?}", block_time.unwrap(), chain_id);
-    let answer_provider = EthersAnswerProvider {
-        provider: resolved_opts.provider
+    println!("Head block: {:?} at {:?}, Chain ID: {:?}", head.number.unwrap_or_default(), head.timestamp, chain_id);
+    match finalized {
+        Ok(Some(block)) => println!("Finalized block: {:?} at {:?}", block.number.unwrap_or_default(), block.timestamp),
+        Ok(None) => println!("Finalized block: unavailable"),
+        Err(e) => println!("Finalized block: unavailable ({})", e),
+    }
+    if chain_id.as_u64() != resolved_opts.chain_id {
+        return Err(format!(
+            "RPC endpoint serves chain {}, configured chain is {}",
+            ens::chain::chain_name(chain_id.as_u64()),
+            ens::chain::chain_name(resolved_opts.chain_id)
+        ).into());
+    }
+    println!("ENS registry: {:?}, resolution backend: {:?}, resolving at: {}", resolved_opts.registry, resolved_opts.backend, resolved_opts.block);
+    let light_client = match resolved_opts.light_client {
+        Some(config) => {
+            let light_client = Arc::new(LightClient::new(config).await?);
+            let header = light_client.finalized_execution();
+            println!("Light client bootstrapped at slot {}, resolving at its finalized execution block {} {:?}", light_client.finalized_slot(), header.block_number, header.block_hash);
+            if !resolved_opts.verify_proofs {
+                println!("Light client without --verify-proofs pins blocks, answers still come from the RPC endpoint");
+            }
+            tokio::spawn(light_client.clone().run());
+            Some(light_client)
+        }
+        None => None,
     };
+    let answer_provider = Arc::new(EthersAnswerProvider {
+        provider: resolved_opts.provider,
+        text_records: resolved_opts.text_records,
+        avatar: resolved_opts.avatar,
+        ccip: resolved_opts.ccip,
+        backend: resolved_opts.backend,
+        registry: resolved_opts.registry,
+        registry_fallback: ens::chain::has_registry_fallback(resolved_opts.chain_id, resolved_opts.registry),
+        block: resolved_opts.block,
+        verify_proofs: resolved_opts.verify_proofs,
+        public_resolvers: resolved_opts.public_resolvers,
+        light_client,
+        cache: resolved_opts.answer_cache.then(ens::AnswerCache::default),
+    });
+    tokio::spawn(answer_provider.clone().watch_logs());
+    let dns_config = Arc::new(resolved_opts.dns_config);
+    let socket = Arc::new(socket);
 
     let mut buf = [0u8; 1024];
 
     loop {
         let (size, src) = socket.recv_from(&mut buf).await?;
-        let data = &buf[0..size];
+        let data = buf[0..size].to_vec();
 
-        let response_packet = dns::handle_dns_packet(data.to_vec(), &answer_provider).await;
+        // a slow answer, e.g. from a CCIP-Read gateway, must not hold up other clients
+        let (socket, answer_provider, dns_config) = (socket.clone(), answer_provider.clone(), dns_config.clone());
+        tokio::spawn(async move {
+            let response_packet = dns::handle_dns_packet(data, src.ip(), answer_provider.as_ref(), &dns_config).await;
 
-        if !response_packet.is_empty() {
-            socket.send_to(&response_packet, &src).await?;
-        }
+            if !response_packet.is_empty() {
+                if let Err(e) = socket.send_to(&response_packet, &src).await {
+                    println!("error sending response to {}: {}", src, e);
+                }
+            }
+        });
     }
 }
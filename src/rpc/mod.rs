@@ -0,0 +1,390 @@
+use std::fmt::{Debug, Display};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use ethers::types::U64;
+use futures_util::future::join_all;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Consecutive failures after which an endpoint is only tried once the healthy ones failed.
+const MAX_FAILURES: u32 = 3;
+/// How long an endpoint stays demoted after `MAX_FAILURES`.
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+struct Health {
+    failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Endpoint<T> {
+    name: String,
+    client: T,
+    health: Mutex<Health>,
+}
+
+impl<T> Endpoint<T> {
+    fn is_healthy(&self) -> bool {
+        let health = self.health.lock().unwrap();
+        health.unhealthy_until.is_none_or(|x| Instant::now() >= x)
+    }
+
+    fn record(&self, success: bool) {
+        let mut health = self.health.lock().unwrap();
+        if success {
+            *health = Health::default();
+            return;
+        }
+        health.failures += 1;
+        if health.failures >= MAX_FAILURES {
+            if health.unhealthy_until.is_none() {
+                println!("RPC endpoint {} unhealthy after {} failures", self.name, health.failures);
+            }
+            health.unhealthy_until = Some(Instant::now() + UNHEALTHY_COOLDOWN);
+        }
+    }
+}
+
+/// What a node answered, error responses included so that nodes can agree on a revert.
+#[derive(Debug, Clone)]
+enum Outcome {
+    Result(Value),
+    Error(JsonRpcError),
+}
+
+impl PartialEq for Outcome {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Outcome::Result(a), Outcome::Result(b)) => a == b,
+            (Outcome::Error(a), Outcome::Error(b)) => a.code == b.code && a.message == b.message && a.data == b.data,
+            _ => false,
+        }
+    }
+}
+
+impl Outcome {
+    fn into_result(self) -> Result<Value, RpcPoolError> {
+        match self {
+            Outcome::Result(x) => Ok(x),
+            Outcome::Error(e) => Err(RpcPoolError::JsonRpc(e)),
+        }
+    }
+}
+
+/// Error responses every honest node returns alike, failing over would not change them.
+fn is_final(error: &JsonRpcError) -> bool {
+    error.code == 3 || error.message.contains("revert")
+}
+
+/// Block tags, which endpoints may each resolve to a different block.
+const BLOCK_TAGS: &[&str] = &["latest", "safe", "finalized", "pending", "earliest"];
+
+/// The block tag in `params`, block numbers and hashes are already pinned.
+fn block_tag(params: &Value) -> Option<&str> {
+    match params {
+        Value::Array(values) => values.iter().filter_map(Value::as_str).find(|x| BLOCK_TAGS.contains(x)),
+        _ => None,
+    }
+}
+
+/// Replaces block tags in `params` with `block`.
+fn pin_block(params: &Value, block: U64) -> Value {
+    match params {
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|x| match x.as_str() {
+                    Some(tag) if BLOCK_TAGS.contains(&tag) => Value::String(format!("{:#x}", block)),
+                    _ => x.clone(),
+                })
+                .collect(),
+        ),
+        _ => params.clone(),
+    }
+}
+
+#[derive(Debug)]
+pub enum RpcPoolError {
+    /// An error response, e.g. a revert, that failing over would not change.
+    JsonRpc(JsonRpcError),
+    Serde(serde_json::Error),
+    /// Every endpoint failed, with the last failure.
+    AllFailed(String),
+    NoQuorum { method: String, quorum: usize },
+}
+
+impl Display for RpcPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcPoolError::JsonRpc(e) => write!(f, "{}", e),
+            RpcPoolError::Serde(e) => write!(f, "{}", e),
+            RpcPoolError::AllFailed(e) => write!(f, "all RPC endpoints failed, last error: {}", e),
+            RpcPoolError::NoQuorum { method, quorum } => write!(f, "fewer than {} RPC endpoints agree on {}", quorum, method),
+        }
+    }
+}
+
+impl std::error::Error for RpcPoolError {}
+
+impl RpcError for RpcPoolError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            RpcPoolError::JsonRpc(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            RpcPoolError::Serde(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RpcPoolError> for ProviderError {
+    fn from(e: RpcPoolError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+/// Several RPC endpoints behind one client. Requests fail over from endpoint to endpoint in
+/// order, endpoints that keep failing are demoted for a while. In quorum mode every endpoint is
+/// asked and a result is only returned when `quorum` of them agree on it at the same block.
+#[derive(Debug)]
+pub struct RpcPool<T> {
+    endpoints: Vec<Endpoint<T>>,
+    quorum: Option<usize>,
+    /// Time an endpoint has to answer a request before it counts as failed.
+    timeout: Duration,
+}
+
+impl<T: JsonRpcClient> RpcPool<T> {
+    pub fn new(clients: Vec<(String, T)>, quorum: Option<usize>, timeout: Duration) -> Self {
+        let endpoints = clients
+            .into_iter()
+            .map(|(name, client)| Endpoint { name, client, health: Mutex::new(Health::default()) })
+            .collect();
+        RpcPool { endpoints, quorum, timeout }
+    }
+
+    /// `Err` with a description when the endpoint failed, not when it answered with an error.
+    async fn request_endpoint(&self, endpoint: &Endpoint<T>, method: &str, params: &Value) -> Result<Outcome, String> {
+        let response = match tokio::time::timeout(self.timeout, endpoint.client.request::<_, Value>(method, params)).await {
+            Ok(x) => x,
+            Err(_) => {
+                endpoint.record(false);
+                return Err(format!("{}: timed out after {:?}", endpoint.name, self.timeout));
+            }
+        };
+        match response {
+            Ok(x) => {
+                endpoint.record(true);
+                Ok(Outcome::Result(x))
+            }
+            Err(e) => match e.as_error_response() {
+                Some(response) if is_final(response) => {
+                    endpoint.record(true);
+                    Ok(Outcome::Error(response.clone()))
+                }
+                _ => {
+                    endpoint.record(false);
+                    Err(format!("{}: {}", endpoint.name, e))
+                }
+            },
+        }
+    }
+
+    async fn request_failover(&self, method: &str, params: &Value) -> Result<Value, RpcPoolError> {
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) = self.endpoints.iter().partition(|x| x.is_healthy());
+        let mut last_error = "no RPC endpoints".to_string();
+        for endpoint in healthy.into_iter().chain(unhealthy) {
+            match self.request_endpoint(endpoint, method, params).await {
+                Ok(outcome) => return outcome.into_result(),
+                Err(e) => {
+                    println!("RPC request {} failed on {}", method, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(RpcPoolError::AllFailed(last_error))
+    }
+
+    /// The highest block `tag` is at for at least `quorum` endpoints. Pending blocks differ from
+    /// node to node, `"pending"` is pinned like `"latest"`.
+    async fn quorum_block(&self, quorum: usize, tag: &str) -> Result<U64, RpcPoolError> {
+        let (method, params) = match tag {
+            "earliest" => return Ok(U64::zero()),
+            "latest" | "pending" => ("eth_blockNumber", Value::Array(vec![])),
+            _ => ("eth_getBlockByNumber", Value::Array(vec![Value::String(tag.to_string()), Value::Bool(false)])),
+        };
+        let outcomes = join_all(self.endpoints.iter().map(|x| self.request_endpoint(x, method, &params))).await;
+        let mut blocks: Vec<U64> = outcomes
+            .into_iter()
+            .filter_map(|x| match x {
+                Ok(Outcome::Result(Value::Object(block))) => serde_json::from_value(block.get("number")?.clone()).ok(),
+                Ok(Outcome::Result(value)) => serde_json::from_value(value).ok(),
+                _ => None,
+            })
+            .collect();
+        blocks.sort_by(|a, b| b.cmp(a));
+        blocks.get(quorum - 1).copied().ok_or_else(|| RpcPoolError::NoQuorum { method: method.to_string(), quorum })
+    }
+
+    async fn request_quorum(&self, quorum: usize, method: &str, params: &Value) -> Result<Value, RpcPoolError> {
+        if method == "eth_blockNumber" {
+            let block = self.quorum_block(quorum, "latest").await?;
+            return Ok(Value::String(format!("{:#x}", block)));
+        }
+        let params = match block_tag(params) {
+            Some(tag) => pin_block(params, self.quorum_block(quorum, tag).await?),
+            None => params.clone(),
+        };
+        let outcomes = join_all(self.endpoints.iter().map(|x| self.request_endpoint(x, method, &params))).await;
+
+        let mut votes: Vec<(Outcome, usize)> = vec![];
+        for outcome in outcomes.into_iter().flatten() {
+            match votes.iter_mut().find(|(x, _)| *x == outcome) {
+                Some((_, count)) => *count += 1,
+                None => votes.push((outcome, 1)),
+            }
+        }
+        match votes.into_iter().find(|(_, count)| *count >= quorum) {
+            Some((outcome, _)) => outcome.into_result(),
+            None => Err(RpcPoolError::NoQuorum { method: method.to_string(), quorum }),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient> JsonRpcClient for RpcPool<T> {
+    type Error = RpcPoolError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params).map_err(RpcPoolError::Serde)?;
+        let result = match self.quorum {
+            Some(quorum) => self.request_quorum(quorum, method, &params).await?,
+            None => self.request_failover(method, &params).await?,
+        };
+        serde_json::from_value(result).map_err(RpcPoolError::Serde)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockError, MockProvider, MockResponse};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn pool(mocks: &[MockProvider], quorum: Option<usize>) -> RpcPool<MockProvider> {
+        RpcPool::new(mocks.iter().enumerate().map(|(i, x)| (i.to_string(), x.clone())).collect(), quorum, TIMEOUT)
+    }
+
+    /// An endpoint that never answers.
+    #[derive(Debug)]
+    struct Hanging;
+
+    #[async_trait]
+    impl JsonRpcClient for Hanging {
+        type Error = MockError;
+
+        async fn request<P, R>(&self, _method: &str, _params: P) -> Result<R, Self::Error>
+        where
+            P: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned + Send,
+        {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let pool = RpcPool::new(vec![("hanging".to_string(), Hanging)], None, Duration::from_millis(10));
+        for _ in 0..MAX_FAILURES {
+            let res = pool.request::<_, U64>("eth_chainId", ()).await;
+            assert!(matches!(res, Err(RpcPoolError::AllFailed(_))));
+        }
+        assert!(!pool.endpoints[0].is_healthy());
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let mocks = [MockProvider::new(), MockProvider::new()];
+        let pool = pool(&mocks, None);
+        for _ in 0..MAX_FAILURES {
+            mocks[1].push(U64::from(1)).unwrap();
+            let chain_id: U64 = pool.request("eth_chainId", ()).await.unwrap();
+            assert_eq!(chain_id, U64::from(1));
+        }
+        assert!(!pool.endpoints[0].is_healthy());
+
+        // the demoted endpoint is tried last, reverts are not failed over
+        mocks[0].push(U64::from(1)).unwrap();
+        mocks[1].push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some(Value::String("0x01".to_string())),
+        }));
+        let res = pool.request::<_, Value>("eth_call", ()).await;
+        assert!(matches!(res, Err(RpcPoolError::JsonRpc(_))));
+    }
+
+    #[tokio::test]
+    async fn test_quorum() {
+        let mocks = [MockProvider::new(), MockProvider::new(), MockProvider::new()];
+        // responses are popped from the back
+        for (mock, (block, result)) in mocks.iter().zip([(0x10, "0x01"), (0x12, "0x01"), (0x11, "0x02")]) {
+            mock.push::<&str, _>(result).unwrap();
+            mock.push(U64::from(block)).unwrap();
+        }
+        let pool = pool(&mocks, Some(2));
+        let result: String = pool.request("eth_call", (Value::Null, "latest")).await.unwrap();
+        assert_eq!(result, "0x01");
+        mocks[0].assert_request("eth_blockNumber", Value::Array(vec![])).unwrap();
+        mocks[0].assert_request("eth_call", (Value::Null, "0x11")).unwrap();
+
+        for (mock, result) in mocks.iter().zip(["0x01", "0x02", "0x03"]) {
+            mock.push::<&str, _>(result).unwrap();
+            mock.push(U64::from(0x10)).unwrap();
+        }
+        let res = pool.request::<_, String>("eth_call", (Value::Null, "latest")).await;
+        assert!(matches!(res, Err(RpcPoolError::NoQuorum { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_quorum_pins_finalized() {
+        let mocks = [MockProvider::new(), MockProvider::new(), MockProvider::new()];
+        for (mock, finalized) in mocks.iter().zip([0x8, 0x9, 0x7]) {
+            mock.push::<&str, _>("0x01").unwrap();
+            mock.push(serde_json::json!({ "number": format!("{:#x}", finalized) })).unwrap();
+        }
+        let pool = pool(&mocks, Some(2));
+        let result: String = pool.request("eth_call", (Value::Null, "finalized")).await.unwrap();
+        assert_eq!(result, "0x01");
+        for mock in &mocks {
+            mock.assert_request("eth_getBlockByNumber", ("finalized", false)).unwrap();
+            mock.assert_request("eth_call", (Value::Null, "0x8")).unwrap();
+        }
+
+        // a block given by hash needs no quorum block
+        let block = serde_json::json!({ "blockHash": format!("{:#x}", ethers::types::H256::repeat_byte(1)) });
+        for mock in &mocks {
+            mock.push::<&str, _>("0x01").unwrap();
+        }
+        let result: String = pool.request("eth_call", (Value::Null, &block)).await.unwrap();
+        assert_eq!(result, "0x01");
+        for mock in &mocks {
+            mock.assert_request("eth_call", (Value::Null, &block)).unwrap();
+        }
+    }
+}